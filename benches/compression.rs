@@ -0,0 +1,83 @@
+//! Fragments needed to send some typical messages, with and without compression.
+//!
+//! Run with `cargo bench --features compression`
+
+use std::time::Instant;
+
+use rand::Rng;
+use rustafarian_shared::assembler::{
+    assembler::Assembler, compression::Compression, disassembler::Disassembler,
+};
+use rustafarian_shared::messages::browser_messages::{BrowserResponse, FileId};
+use rustafarian_shared::messages::general_messages::DroneSend;
+
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let mut rng = rand::thread_rng();
+
+    let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor. "
+        .repeat(500);
+    // An uncompressed bitmap with smooth gradients, like a screenshot or a drawing
+    let bitmap: Vec<u8> = (0..100_000u32)
+        .map(|i| ((i % 400) / 4 + (i / 4000) * 2) as u8)
+        .collect();
+    // Already compressed data (e.g. a JPEG), which looks random
+    let noise: Vec<u8> = (0..100_000).map(|_| rng.gen()).collect();
+
+    let messages = [
+        (
+            "text file (40 KB)",
+            BrowserResponse::TextFile(FileId(1), text),
+        ),
+        (
+            "bitmap media (100 KB)",
+            BrowserResponse::MediaFile(FileId(2), bitmap),
+        ),
+        (
+            "jpeg-like media (100 KB)",
+            BrowserResponse::MediaFile(FileId(3), noise),
+        ),
+    ];
+
+    println!(
+        "{:<26} {:>10} {:>10} {:>8} {:>14} {:>14}",
+        "message", "plain", "lz", "ratio", "compress", "decompress"
+    );
+    for (name, message) in messages {
+        let payload = message.stringify().into_bytes();
+
+        let plain = Disassembler::new().disassemble_message(payload.clone(), 0);
+        let mut disassembler = Disassembler::new();
+        disassembler.set_compression(Compression::Lz);
+
+        let start = Instant::now();
+        let mut compressed = Vec::new();
+        for session_id in 0..ITERATIONS {
+            compressed = disassembler.disassemble_message(payload.clone(), session_id.into());
+        }
+        let compress_time = start.elapsed() / ITERATIONS;
+
+        let start = Instant::now();
+        for session_id in 0..ITERATIONS {
+            let mut assembler = Assembler::new();
+            assembler.set_compression(Compression::Lz);
+            let mut result = None;
+            for fragment in &compressed {
+                result = assembler.add_fragment(fragment.clone(), u64::from(session_id));
+            }
+            assert_eq!(result.as_ref(), Some(&payload));
+        }
+        let decompress_time = start.elapsed() / ITERATIONS;
+
+        println!(
+            "{:<26} {:>10} {:>10} {:>7.1}% {:>14?} {:>14?}",
+            name,
+            plain.len(),
+            compressed.len(),
+            compressed.len() as f64 / plain.len() as f64 * 100.0,
+            compress_time,
+            decompress_time
+        );
+    }
+}
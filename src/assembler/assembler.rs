@@ -1,5 +1,6 @@
 #![allow(unused)]
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant};
 use std::{fs, thread};
 use wg_2024::config::Config;
 use wg_2024::controller::{DroneCommand, DroneEvent};
@@ -8,9 +9,217 @@ use wg_2024::network::{NodeId, SourceRoutingHeader};
 use wg_2024::packet::{FloodRequest, FloodResponse, Fragment, NodeType};
 use wg_2024::packet::{Packet, PacketType};
 
+use crate::TIMEOUT_TIMER_MS;
+
+#[cfg(feature = "compression")]
+use super::compression::Compression;
+use super::disassembler::{DEFAULT_MAX_FRAGMENTS, FRAGMENT_DSIZE};
+
+/// A message that has not been completely received yet
+///
+/// * `total_n_fragments: u64` - the number of fragments the message is made of
+/// * `fragments: BTreeMap<u64, Fragment>` - the fragments received so far, indexed by `fragment_index`
+/// * `first_seen: Instant` - when the first fragment of the session arrived
+/// * `last_seen: Instant` - when the latest fragment of the session arrived
+struct PartialMessage {
+    total_n_fragments: u64,
+    fragments: BTreeMap<u64, Fragment>,
+    first_seen: Instant,
+    last_seen: Instant,
+}
+
+/// Identifies a message being reassembled. Session ids are only unique per sender, so the
+/// source node is part of the key: sessions with the same id coming from different nodes are
+/// kept separate.
+///
+/// * `source_id: Option<NodeId>` - the node that sent the message, `None` if unknown
+/// * `session_id: u64` - the session id of the packets
+///
+/// A bare `u64` converts into a key without source, a `(NodeId, u64)` into a key with source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionKey {
+    pub source_id: Option<NodeId>,
+    pub session_id: u64,
+}
+
+impl SessionKey {
+    #[must_use]
+    pub fn new(source_id: NodeId, session_id: u64) -> Self {
+        SessionKey {
+            source_id: Some(source_id),
+            session_id,
+        }
+    }
+
+    /// The key of the session a packet belongs to, the source being the first hop of its route
+    #[must_use]
+    pub fn from_packet(packet: &Packet) -> Self {
+        SessionKey {
+            source_id: packet.routing_header.hops.first().copied(),
+            session_id: packet.session_id,
+        }
+    }
+}
+
+impl From<u64> for SessionKey {
+    fn from(session_id: u64) -> Self {
+        SessionKey {
+            source_id: None,
+            session_id,
+        }
+    }
+}
+
+impl From<(NodeId, u64)> for SessionKey {
+    fn from((source_id, session_id): (NodeId, u64)) -> Self {
+        SessionKey::new(source_id, session_id)
+    }
+}
+
+/// Progress of a session that is still waiting for fragments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionProgress {
+    pub source_id: Option<NodeId>,
+    pub session_id: u64,
+    pub received: u64,
+    pub total_n_fragments: u64,
+}
+
+/// Reasons why a fragment can be rejected by the assembler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblerError {
+    /// The `total_n_fragments` of the fragment differs from the one of the fragments already received
+    InconsistentTotal {
+        session_id: u64,
+        expected: u64,
+        found: u64,
+    },
+    /// The `fragment_index` is not lower than `total_n_fragments`
+    IndexOutOfRange {
+        session_id: u64,
+        fragment_index: u64,
+        total_n_fragments: u64,
+    },
+    /// The `length` of the fragment is bigger than `FRAGMENT_DSIZE`
+    InvalidLength {
+        session_id: u64,
+        fragment_index: u64,
+        length: u8,
+    },
+    /// A fragment with the same index, but a different payload, was already received
+    ConflictingDuplicate {
+        session_id: u64,
+        fragment_index: u64,
+    },
+    /// The `total_n_fragments` is bigger than `AssemblerConfig::max_fragments_per_session`
+    TooManyFragments {
+        session_id: u64,
+        total_n_fragments: u64,
+        max: u64,
+    },
+    /// The fragment does not fit in the limits of the `AssemblerConfig`, even after evicting
+    /// every other session
+    LimitExceeded { session_id: u64 },
+    /// The compressed data of the message is corrupted or too big
+    InvalidCompression { session_id: u64 },
+}
+
+impl std::fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssemblerError::InconsistentTotal {
+                session_id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "session {session_id}: expected {expected} total fragments, found {found}"
+            ),
+            AssemblerError::IndexOutOfRange {
+                session_id,
+                fragment_index,
+                total_n_fragments,
+            } => write!(
+                f,
+                "session {session_id}: fragment index {fragment_index} out of range for {total_n_fragments} fragments"
+            ),
+            AssemblerError::InvalidLength {
+                session_id,
+                fragment_index,
+                length,
+            } => write!(
+                f,
+                "session {session_id}: fragment {fragment_index} has length {length}, max is {FRAGMENT_DSIZE}"
+            ),
+            AssemblerError::ConflictingDuplicate {
+                session_id,
+                fragment_index,
+            } => write!(
+                f,
+                "session {session_id}: fragment {fragment_index} received twice with different data"
+            ),
+            AssemblerError::TooManyFragments {
+                session_id,
+                total_n_fragments,
+                max,
+            } => write!(
+                f,
+                "session {session_id}: {total_n_fragments} fragments exceed the limit of {max}"
+            ),
+            AssemblerError::InvalidCompression { session_id } => {
+                write!(f, "session {session_id}: invalid compressed message")
+            }
+            AssemblerError::LimitExceeded { session_id } => {
+                write!(f, "session {session_id}: assembler memory limit exceeded")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+/// Limits on the memory used by the assembler, to protect a node from peers opening too many
+/// sessions or announcing huge messages
+///
+/// * `session_timeout: Duration` - how long a session can go without new fragments before `sweep_expired` evicts it
+/// * `max_sessions: usize` - maximum number of concurrent incomplete sessions, when exceeded the least recently seen session is evicted
+/// * `max_fragments_per_session: u64` - fragments announcing a bigger `total_n_fragments` are rejected
+/// * `max_buffered_bytes: usize` - maximum memory used by the buffered fragments (each one accounts for `FRAGMENT_DSIZE` bytes),
+///   when exceeded the least recently seen sessions are evicted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssemblerConfig {
+    pub session_timeout: Duration,
+    pub max_sessions: usize,
+    pub max_fragments_per_session: u64,
+    pub max_buffered_bytes: usize,
+}
+
+impl Default for AssemblerConfig {
+    fn default() -> Self {
+        AssemblerConfig {
+            session_timeout: Duration::from_millis(TIMEOUT_TIMER_MS),
+            max_sessions: 1024,
+            max_fragments_per_session: DEFAULT_MAX_FRAGMENTS,
+            max_buffered_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
 pub struct Assembler {
-    //map every fragment set with it's sessionID
-    received_fragment: HashMap<u64, Vec<Fragment>>,
+    //map every fragment set with it's source and sessionID
+    received_fragment: HashMap<SessionKey, PartialMessage>,
+    config: AssemblerConfig,
+    // bytes used by the fragments currently stored
+    buffered_bytes: usize,
+    // number of retransmitted fragments that were already received
+    duplicate_fragments: u64,
+    // number of fragments refused because invalid or exceeding the limits
+    rejected_fragments: u64,
+    // number of incomplete sessions dropped to make room for new fragments
+    evicted_sessions: u64,
+    // compression used by the senders, reversed after reassembling a message
+    #[cfg(feature = "compression")]
+    compression: Compression,
 }
 
 impl Default for Assembler {
@@ -20,45 +229,373 @@ impl Default for Assembler {
 }
 
 impl Assembler {
+    /// Create a new assembler with the default `AssemblerConfig`, whose sessions expire after
+    /// `TIMEOUT_TIMER_MS` without new fragments
     #[must_use]
     pub fn new() -> Self {
+        Self::with_config(AssemblerConfig::default())
+    }
+
+    /// Create a new assembler with a custom session timeout
+    #[must_use]
+    pub fn with_timeout(session_timeout: Duration) -> Self {
+        Self::with_config(AssemblerConfig {
+            session_timeout,
+            ..AssemblerConfig::default()
+        })
+    }
+
+    /// Create a new assembler with custom limits
+    #[must_use]
+    pub fn with_config(config: AssemblerConfig) -> Self {
         Assembler {
             received_fragment: HashMap::new(),
+            config,
+            buffered_bytes: 0,
+            duplicate_fragments: 0,
+            rejected_fragments: 0,
+            evicted_sessions: 0,
+            #[cfg(feature = "compression")]
+            compression: Compression::None,
         }
     }
 
-    //adds the fragment based on the session id and returns it if the message is complete
-    pub fn add_fragment(&mut self, fragment: Fragment, session_id: u64) -> Option<Vec<u8>> {
-        //check if session id already present or create empty entry with new Vec
-        let fragments = self.received_fragment.entry(session_id).or_default();
+    #[must_use]
+    pub fn config(&self) -> &AssemblerConfig {
+        &self.config
+    }
 
-        //push the fragment in the right vec
-        fragments.push(fragment);
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// With `Compression::Lz`, the messages starting with a compression header are
+    /// decompressed and the other ones are returned as they are, so senders with and without
+    /// compression can be mixed. By default the messages are never decompressed, since raw
+    /// payloads could start with a header byte. The `MessageAssembler` always decompresses
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
 
-        //if the fragments lenght == field total_n_fragments => reassemble message and return
-        if let Some(total_fragment) = fragments.first().map(|f| f.total_n_fragments) {
-            if fragments.len() == total_fragment as usize {
-                return Some(self.reassemble_message(session_id));
+    /// The time a session can go without receiving fragments before `sweep_expired` evicts it
+    #[must_use]
+    pub fn session_timeout(&self) -> Duration {
+        self.config.session_timeout
+    }
+
+    //adds the fragment based on the session (id, optionally with its source) and returns it
+    //if the message is complete.
+    //invalid fragments are dropped, use try_add_fragment to know why
+    pub fn add_fragment(
+        &mut self,
+        fragment: Fragment,
+        session: impl Into<SessionKey>,
+    ) -> Option<Vec<u8>> {
+        self.add_fragment_at(fragment, session, Instant::now())
+    }
+
+    /// Same as `add_fragment`, but uses `now` as the time the fragment was received
+    pub fn add_fragment_at(
+        &mut self,
+        fragment: Fragment,
+        session: impl Into<SessionKey>,
+        now: Instant,
+    ) -> Option<Vec<u8>> {
+        self.try_add_fragment_at(fragment, session, now)
+            .ok()
+            .flatten()
+    }
+
+    /// Adds the fragment to its session, validating it against the fragments already received
+    /// and the limits of the `AssemblerConfig`.
+    /// Returns the message if it is complete, or an error if the fragment is rejected, in which
+    /// case the session is left untouched and can be dropped with `discard_session`
+    pub fn try_add_fragment(
+        &mut self,
+        fragment: Fragment,
+        session: impl Into<SessionKey>,
+    ) -> Result<Option<Vec<u8>>, AssemblerError> {
+        self.try_add_fragment_at(fragment, session, Instant::now())
+    }
+
+    /// Same as `try_add_fragment`, but uses `now` as the time the fragment was received
+    pub fn try_add_fragment_at(
+        &mut self,
+        fragment: Fragment,
+        session: impl Into<SessionKey>,
+        now: Instant,
+    ) -> Result<Option<Vec<u8>>, AssemblerError> {
+        let result = self.insert_fragment(fragment, session.into(), now);
+        if result.is_err() {
+            self.rejected_fragments += 1;
+        }
+        result
+    }
+
+    fn insert_fragment(
+        &mut self,
+        fragment: Fragment,
+        key: SessionKey,
+        now: Instant,
+    ) -> Result<Option<Vec<u8>>, AssemblerError> {
+        let session_id = key.session_id;
+        if fragment.length as usize > FRAGMENT_DSIZE {
+            return Err(AssemblerError::InvalidLength {
+                session_id,
+                fragment_index: fragment.fragment_index,
+                length: fragment.length,
+            });
+        }
+
+        let total = self
+            .received_fragment
+            .get(&key)
+            .map_or(fragment.total_n_fragments, |partial| {
+                partial.total_n_fragments
+            });
+        if fragment.total_n_fragments != total {
+            return Err(AssemblerError::InconsistentTotal {
+                session_id,
+                expected: total,
+                found: fragment.total_n_fragments,
+            });
+        }
+        if fragment.fragment_index >= total {
+            return Err(AssemblerError::IndexOutOfRange {
+                session_id,
+                fragment_index: fragment.fragment_index,
+                total_n_fragments: total,
+            });
+        }
+        if total > self.config.max_fragments_per_session {
+            return Err(AssemblerError::TooManyFragments {
+                session_id,
+                total_n_fragments: total,
+                max: self.config.max_fragments_per_session,
+            });
+        }
+
+        //a retransmitted fragment must not be counted twice, keep the first copy
+        if let Some(partial) = self.received_fragment.get_mut(&key) {
+            if let Some(previous) = partial.fragments.get(&fragment.fragment_index) {
+                let length = previous.length as usize;
+                if previous.length != fragment.length
+                    || previous.data[..length] != fragment.data[..length]
+                {
+                    return Err(AssemblerError::ConflictingDuplicate {
+                        session_id,
+                        fragment_index: fragment.fragment_index,
+                    });
+                }
+                partial.last_seen = now;
+                self.duplicate_fragments += 1;
+                return Ok(None);
+            }
+        }
+
+        self.make_room(key)?;
+
+        //check if session id already present or create empty entry
+        let partial = self
+            .received_fragment
+            .entry(key)
+            .or_insert_with(|| PartialMessage {
+                total_n_fragments: total,
+                fragments: BTreeMap::new(),
+                first_seen: now,
+                last_seen: now,
+            });
+        partial.last_seen = now;
+        partial.fragments.insert(fragment.fragment_index, fragment);
+        self.buffered_bytes += FRAGMENT_DSIZE;
+
+        //the indexes are unique and lower than total_n_fragments, so if there are
+        //total_n_fragments of them the message is complete => reassemble message and return
+        if partial.fragments.len() as u64 == total {
+            return self.reassemble_message(key).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Evict the least recently seen sessions until a new fragment of `key` fits in the limits
+    fn make_room(&mut self, key: SessionKey) -> Result<(), AssemblerError> {
+        let session_id = key.session_id;
+        if !self.received_fragment.contains_key(&key) {
+            while self.received_fragment.len() >= self.config.max_sessions {
+                if !self.evict_oldest(key) {
+                    return Err(AssemblerError::LimitExceeded { session_id });
+                }
             }
         }
-        None
+        while self.buffered_bytes + FRAGMENT_DSIZE > self.config.max_buffered_bytes {
+            if !self.evict_oldest(key) {
+                return Err(AssemblerError::LimitExceeded { session_id });
+            }
+        }
+        Ok(())
+    }
+
+    /// Evict the least recently seen session other than `except`, returns `false` if there is none
+    fn evict_oldest(&mut self, except: SessionKey) -> bool {
+        let oldest = self
+            .received_fragment
+            .iter()
+            .filter(|(&key, _)| key != except)
+            .min_by_key(|(_, partial)| partial.last_seen)
+            .map(|(&key, _)| key);
+
+        match oldest {
+            Some(key) => {
+                self.remove_session(key);
+                self.evicted_sessions += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a session, releasing the memory accounted for its fragments
+    fn remove_session(&mut self, key: SessionKey) -> Option<PartialMessage> {
+        let partial = self.received_fragment.remove(&key)?;
+        self.buffered_bytes -= partial.fragments.len() * FRAGMENT_DSIZE;
+        Some(partial)
+    }
+
+    /// Drop every fragment received for a session, e.g. after it was found to be corrupted.
+    /// Returns `false` if the session was not pending
+    pub fn discard_session(&mut self, session: impl Into<SessionKey>) -> bool {
+        self.remove_session(session.into()).is_some()
+    }
+
+    /// Remove every session that has not received a fragment for longer than the session timeout.
+    /// Returns the keys of the evicted sessions, so that the node can decide whether to ask for a
+    /// retransmission or give up on the message
+    pub fn sweep_expired(&mut self, now: Instant) -> Vec<SessionKey> {
+        let timeout = self.config.session_timeout;
+        let expired: Vec<SessionKey> = self
+            .received_fragment
+            .iter()
+            .filter(|(_, partial)| now.saturating_duration_since(partial.last_seen) >= timeout)
+            .map(|(&key, _)| key)
+            .collect();
+
+        for key in &expired {
+            self.remove_session(*key);
+        }
+        expired
+    }
+
+    /// When the first fragment of a session was received, `None` if the session is not pending
+    #[must_use]
+    pub fn first_seen(&self, session: impl Into<SessionKey>) -> Option<Instant> {
+        self.received_fragment
+            .get(&session.into())
+            .map(|partial| partial.first_seen)
+    }
+
+    /// When the latest fragment of a session was received, `None` if the session is not pending
+    #[must_use]
+    pub fn last_seen(&self, session: impl Into<SessionKey>) -> Option<Instant> {
+        self.received_fragment
+            .get(&session.into())
+            .map(|partial| partial.last_seen)
+    }
+
+    /// The indexes of the fragments of a session that have not been received yet, in ascending
+    /// order. Returns `None` if the session is not pending
+    #[must_use]
+    pub fn missing_fragments(&self, session: impl Into<SessionKey>) -> Option<Vec<u64>> {
+        self.received_fragment.get(&session.into()).map(|partial| {
+            (0..partial.total_n_fragments)
+                .filter(|index| !partial.fragments.contains_key(index))
+                .collect()
+        })
+    }
+
+    /// How many fragments of a session have been received out of the total
+    #[must_use]
+    pub fn session_progress(&self, session: impl Into<SessionKey>) -> Option<SessionProgress> {
+        let key = session.into();
+        self.received_fragment
+            .get(&key)
+            .map(|partial| Self::progress(key, partial))
+    }
+
+    /// Iterate over the progress of every session that is still incomplete
+    pub fn incomplete_sessions(&self) -> impl Iterator<Item = SessionProgress> + '_ {
+        self.received_fragment
+            .iter()
+            .map(|(&key, partial)| Self::progress(key, partial))
+    }
+
+    fn progress(key: SessionKey, partial: &PartialMessage) -> SessionProgress {
+        SessionProgress {
+            source_id: key.source_id,
+            session_id: key.session_id,
+            received: partial.fragments.len() as u64,
+            total_n_fragments: partial.total_n_fragments,
+        }
     }
 
-    fn reassemble_message(&mut self, session_id: u64) -> Vec<u8> {
-        //take the fragments corresponding to session id
-        if let Some(mut fragments) = self.received_fragment.remove(&session_id) {
-            //sort the fragment by index
-            fragments.sort_by_key(|f| f.fragment_index);
+    /// Number of fragments that were ignored because they had already been received
+    #[must_use]
+    pub fn duplicate_fragments(&self) -> u64 {
+        self.duplicate_fragments
+    }
+
+    /// Number of fragments refused because invalid or exceeding the limits of the `AssemblerConfig`
+    #[must_use]
+    pub fn rejected_fragments(&self) -> u64 {
+        self.rejected_fragments
+    }
+
+    /// Number of incomplete sessions dropped to make room for new fragments
+    #[must_use]
+    pub fn evicted_sessions(&self) -> u64 {
+        self.evicted_sessions
+    }
 
+    /// Bytes currently used by the buffered fragments
+    #[must_use]
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes
+    }
+
+    /// Number of sessions still waiting for fragments
+    #[must_use]
+    pub fn pending_sessions(&self) -> usize {
+        self.received_fragment.len()
+    }
+
+    fn reassemble_message(&mut self, key: SessionKey) -> Result<Vec<u8>, AssemblerError> {
+        //take the fragments corresponding to the session
+        if let Some(partial) = self.remove_session(key) {
+            //the fragments are already sorted by index
             let mut message = Vec::new();
-            for fragment in fragments {
+            for fragment in partial.fragments.into_values() {
                 //add fragment data to message
                 message.extend_from_slice(&fragment.data[..fragment.length as usize]);
             }
 
-            return message;
+            //reverse the compression applied by the disassembler, if any
+            #[cfg(feature = "compression")]
+            let message = {
+                let max_size = usize::try_from(self.config.max_fragments_per_session)
+                    .unwrap_or(usize::MAX)
+                    .saturating_mul(FRAGMENT_DSIZE);
+                self.compression.reverse(message, max_size).ok_or(
+                    AssemblerError::InvalidCompression {
+                        session_id: key.session_id,
+                    },
+                )?
+            };
+
+            return Ok(message);
         }
 
-        Vec::new() //to fix
+        Ok(Vec::new()) //to fix
     }
 }
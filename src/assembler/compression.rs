@@ -0,0 +1,205 @@
+//! Optional compression of the messages, applied by the `Disassembler` before splitting a message
+//! into fragments and reversed by the `Assembler` after reassembling it.
+//!
+//! The compression is negotiated by the first byte of the message: with `Compression::Lz` every
+//! message starts with a header byte, `COMPRESSED_HEADER` if it was compressed,
+//! `UNCOMPRESSED_HEADER` if compressing would have made it bigger. Messages serialized with a
+//! `Codec` never start with these bytes, so the `MessageAssembler` always detects compressed
+//! messages, and nodes with and without compression can send to the same receiver. The raw
+//! `Assembler` carries arbitrary payloads, which could start with a header byte: it only looks
+//! for the header when `Assembler::set_compression` is called with `Compression::Lz`.
+//!
+//! The codec is a simple LZ77 variant. After the header, the length of the original message is
+//! stored as a varint, followed by a sequence of tokens:
+//! * `0b0xxx_xxxx` - a run of `x + 1` literal bytes, which follow the token
+//! * `0b1xxx_xxxx` - a match of `x + MIN_MATCH` bytes, copied from the output `offset` bytes
+//!   back, where `offset` is the u16 (little endian) following the token
+
+/// First byte of a compressed message
+pub const COMPRESSED_HEADER: u8 = 0xC1;
+
+/// First byte of a message sent as it is by a node using `Compression::Lz`
+pub const UNCOMPRESSED_HEADER: u8 = 0xC2;
+
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 0x7F + MIN_MATCH;
+const MAX_LITERALS: usize = 0x80;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+/// Compression applied to the outgoing messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Messages are sent as they are
+    #[default]
+    None,
+    /// Messages are compressed with the LZ codec, unless that makes them bigger
+    Lz,
+}
+
+impl Compression {
+    /// Compress the message, if that makes it smaller, adding the header byte
+    #[must_use]
+    pub fn apply(self, message: Vec<u8>) -> Vec<u8> {
+        match self {
+            Compression::None => message,
+            Compression::Lz => {
+                let compressed = compress(&message);
+                if compressed.len() <= message.len() {
+                    compressed
+                } else {
+                    let mut uncompressed = Vec::with_capacity(message.len() + 1);
+                    uncompressed.push(UNCOMPRESSED_HEADER);
+                    uncompressed.extend_from_slice(&message);
+                    uncompressed
+                }
+            }
+        }
+    }
+
+    /// Reverse `apply`. With `Compression::Lz`, messages without a header byte were sent by a
+    /// node not using compression and are returned as they are. `None` if the compressed data
+    /// is corrupted or bigger than `max_size` bytes
+    #[must_use]
+    pub fn reverse(self, mut message: Vec<u8>, max_size: usize) -> Option<Vec<u8>> {
+        match (self, message.first()) {
+            (Compression::None, _) => Some(message),
+            (Compression::Lz, Some(&COMPRESSED_HEADER)) => decompress(&message, max_size),
+            (Compression::Lz, Some(&UNCOMPRESSED_HEADER)) => {
+                message.remove(0);
+                Some(message)
+            }
+            (Compression::Lz, _) => Some(message),
+        }
+    }
+}
+
+/// Whether the message was compressed with `compress`
+#[must_use]
+pub fn is_compressed(message: &[u8]) -> bool {
+    message.first() == Some(&COMPRESSED_HEADER)
+}
+
+/// Compress a message, prepending the `COMPRESSED_HEADER`
+#[must_use]
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = vec![COMPRESSED_HEADER];
+    write_varint(&mut output, input.len() as u64);
+
+    // Last position where each (hashed) 4 bytes sequence was found
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut literals_start = 0;
+    let mut i = 0;
+
+    while i + MIN_MATCH <= input.len() {
+        let hash = hash(&input[i..i + MIN_MATCH]);
+        let candidate = table[hash];
+        table[hash] = i;
+
+        if candidate != usize::MAX
+            && i - candidate <= MAX_OFFSET
+            && input[candidate..candidate + MIN_MATCH] == input[i..i + MIN_MATCH]
+        {
+            let mut length = MIN_MATCH;
+            while i + length < input.len()
+                && length < MAX_MATCH
+                && input[candidate + length] == input[i + length]
+            {
+                length += 1;
+            }
+
+            write_literals(&mut output, &input[literals_start..i]);
+            output.push(0x80 | (length - MIN_MATCH) as u8);
+            output.extend_from_slice(&((i - candidate) as u16).to_le_bytes());
+
+            i += length;
+            literals_start = i;
+        } else {
+            i += 1;
+        }
+    }
+    write_literals(&mut output, &input[literals_start..]);
+
+    output
+}
+
+/// Decompress a message produced by `compress`. Returns `None` if the data is corrupted or the
+/// original message is bigger than `max_size`
+#[must_use]
+pub fn decompress(input: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    if !is_compressed(input) {
+        return None;
+    }
+    let mut position = 1;
+    let size = usize::try_from(read_varint(input, &mut position)?).ok()?;
+    if size > max_size {
+        return None;
+    }
+
+    // the size is sent by the peer: only reserve what the input can produce, the rest on demand
+    let mut output = Vec::with_capacity(size.min(input.len().saturating_mul(MAX_MATCH)));
+    while position < input.len() {
+        let token = input[position];
+        position += 1;
+
+        if token & 0x80 == 0 {
+            let count = token as usize + 1;
+            let literals = input.get(position..position + count)?;
+            output.extend_from_slice(literals);
+            position += count;
+        } else {
+            let length = (token & 0x7F) as usize + MIN_MATCH;
+            let offset = u16::from_le_bytes([*input.get(position)?, *input.get(position + 1)?]);
+            position += 2;
+
+            let offset = offset as usize;
+            if offset == 0 || offset > output.len() {
+                return None;
+            }
+            // The match can overlap the bytes it produces, so copy one byte at a time
+            let start = output.len() - offset;
+            for j in 0..length {
+                output.push(output[start + j]);
+            }
+        }
+
+        if output.len() > size {
+            return None;
+        }
+    }
+
+    (output.len() == size).then_some(output)
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn write_literals(output: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERALS) {
+        output.push((chunk.len() - 1) as u8);
+        output.extend_from_slice(chunk);
+    }
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &[u8], position: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *input.get(*position)?;
+        *position += 1;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
@@ -8,9 +8,71 @@ use wg_2024::network::{NodeId, SourceRoutingHeader};
 use wg_2024::packet::{FloodRequest, FloodResponse, Fragment, NodeType};
 use wg_2024::packet::{Packet, PacketType};
 
+use crate::messages::general_messages::{Codec, DroneSend, DroneSendError};
+use crate::topology::Topology;
+
+#[cfg(feature = "compression")]
+use super::compression::Compression;
+use super::session_id_allocator::SessionIdAllocator;
+
 pub const FRAGMENT_DSIZE: usize = 128;
 
-pub struct Disassembler {}
+// the length of a fragment is stored in a u8
+const _: () = assert!(FRAGMENT_DSIZE <= u8::MAX as usize);
+
+/// Default maximum number of fragments of a message, shared by the sender
+/// (`DEFAULT_MAX_MESSAGE_SIZE`) and the receiver (`AssemblerConfig::max_fragments_per_session`)
+pub const DEFAULT_MAX_FRAGMENTS: u64 = 65_536;
+
+/// Default maximum size of a message as sent, after compression: the biggest one the default
+/// `AssemblerConfig` accepts
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = DEFAULT_MAX_FRAGMENTS as usize * FRAGMENT_DSIZE;
+
+/// Reasons why a message could not be split into fragments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisassemblerError {
+    /// The message, once compressed, is bigger than the maximum message size of the disassembler
+    MessageTooLarge { size: usize, max: usize },
+    /// No route to the destination was found in the topology
+    Unreachable { destination_id: NodeId },
+    /// The disassembler can not allocate session ids, it was not created for a node
+    NoSessionIdAllocator,
+    /// The message could not be serialized
+    Serialization(DroneSendError),
+}
+
+impl std::fmt::Display for DisassemblerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisassemblerError::MessageTooLarge { size, max } => {
+                write!(f, "message of {size} bytes exceeds the maximum of {max}")
+            }
+            DisassemblerError::Unreachable { destination_id } => {
+                write!(f, "no route to node {destination_id}")
+            }
+            DisassemblerError::NoSessionIdAllocator => {
+                write!(f, "the disassembler has no session id allocator")
+            }
+            DisassemblerError::Serialization(error) => {
+                write!(f, "could not serialize the message: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DisassemblerError {}
+
+pub struct Disassembler {
+    // allocates the session ids of the messages, when the node id is known
+    session_ids: Option<SessionIdAllocator>,
+    // messages bigger than this are refused by the checked methods
+    max_message_size: usize,
+    // wire format of the messages serialized by the disassembler
+    codec: Codec,
+    // compression applied to the messages before splitting them
+    #[cfg(feature = "compression")]
+    compression: Compression,
+}
 
 impl Default for Disassembler {
     fn default() -> Self {
@@ -21,19 +83,119 @@ impl Default for Disassembler {
 impl Disassembler {
     //create hashmap empty
     pub fn new() -> Self {
-        Disassembler {}
+        Disassembler {
+            session_ids: None,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            codec: Codec::Json,
+            #[cfg(feature = "compression")]
+            compression: Compression::None,
+        }
+    }
+
+    /// Create a disassembler that allocates unique session ids for the node
+    #[must_use]
+    pub fn for_node(node_id: NodeId) -> Self {
+        Self::with_session_ids(SessionIdAllocator::new(node_id))
     }
 
+    /// Create a disassembler using an existing (e.g. restored) session id allocator
+    #[must_use]
+    pub fn with_session_ids(session_ids: SessionIdAllocator) -> Self {
+        Disassembler {
+            session_ids: Some(session_ids),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            codec: Codec::Json,
+            #[cfg(feature = "compression")]
+            compression: Compression::None,
+        }
+    }
+
+    #[must_use]
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Set the maximum size of the messages accepted by the checked methods
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+
+    #[must_use]
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// Set the codec used to serialize the messages, the receiver detects it from the payload
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    #[cfg(feature = "compression")]
+    #[must_use]
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Set the compression applied to the messages. The `MessageAssembler` of the receiver
+    /// detects it from the header byte, a raw `Assembler` needs `Compression::Lz` too
+    #[cfg(feature = "compression")]
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// The session id allocator, to persist it. `None` if the disassembler was created with `new`
+    #[must_use]
+    pub fn session_ids(&self) -> Option<&SessionIdAllocator> {
+        self.session_ids.as_ref()
+    }
+
+    /// Allocate a new session id. `None` if the disassembler was created with `new`
+    pub fn next_session_id(&mut self) -> Option<u64> {
+        self.session_ids.as_mut().map(SessionIdAllocator::next_id)
+    }
+
+    //split the message into fragments, ignoring the maximum message size.
+    //an empty message is sent as a single empty fragment, so that the receiver gets it
     pub fn disassemble_message(&mut self, message: Vec<u8>, session_id: u64) -> Vec<Fragment> {
+        let message = self.compress(message);
+        Self::split(&message)
+    }
+
+    /// Same as `disassemble_message`, but fails if the message, once compressed, is bigger
+    /// than the maximum size
+    pub fn try_disassemble_message(
+        &mut self,
+        message: Vec<u8>,
+        session_id: u64,
+    ) -> Result<Vec<Fragment>, DisassemblerError> {
+        let message = self.compress(message);
+        if message.len() > self.max_message_size {
+            return Err(DisassemblerError::MessageTooLarge {
+                size: message.len(),
+                max: self.max_message_size,
+            });
+        }
+        Ok(Self::split(&message))
+    }
+
+    //apply the compression of the disassembler, if any
+    fn compress(&self, message: Vec<u8>) -> Vec<u8> {
+        #[cfg(feature = "compression")]
+        let message = self.compression.apply(message);
+        message
+    }
+
+    //split the message into fragments, at least one
+    fn split(message: &[u8]) -> Vec<Fragment> {
         let mut fragments = Vec::<Fragment>::new();
-        //ceil rounds the decimal number to the next whole number
-        let total_fragments = (message.len() as f64 / FRAGMENT_DSIZE as f64).ceil() as u64;
+        //round up to the next whole number, at least one fragment is always sent
+        let total_fragments = message.len().div_ceil(FRAGMENT_DSIZE).max(1) as u64;
 
         // Break the message into fragments (chunks) and iter over it
         for (i, chunk) in message.chunks(FRAGMENT_DSIZE).enumerate() {
             //divide the message in chunks
             let mut data = [0u8; FRAGMENT_DSIZE];
-            let length = chunk.len() as u8; // chunk length
+            let length = chunk.len() as u8; // chunk length, FRAGMENT_DSIZE fits in u8
 
             // copy chucnk into data
             data[..length as usize].copy_from_slice(chunk);
@@ -49,6 +211,68 @@ impl Disassembler {
             fragments.push(fragment);
         }
 
+        if fragments.is_empty() {
+            fragments.push(Fragment {
+                fragment_index: 0,
+                total_n_fragments: 1,
+                length: 0,
+                data: [0u8; FRAGMENT_DSIZE],
+            });
+        }
+
         fragments
     }
+
+    /// Serialize a message with the codec of the disassembler and split it into `MsgFragment`
+    /// packets, ready to be sent along the `routing_header`
+    pub fn disassemble_packets<M: DroneSend>(
+        &mut self,
+        message: &M,
+        routing_header: SourceRoutingHeader,
+        session_id: u64,
+    ) -> Result<Vec<Packet>, DisassemblerError> {
+        let payload = message
+            .encode(self.codec)
+            .map_err(DisassemblerError::Serialization)?;
+        let fragments = self.try_disassemble_message(payload, session_id)?;
+        Ok(fragments
+            .into_iter()
+            .map(|fragment| Packet {
+                routing_header: routing_header.clone(),
+                session_id,
+                pack_type: PacketType::MsgFragment(fragment),
+            })
+            .collect())
+    }
+
+    /// Same as `disassemble_packets`, but the route from `source_id` to `destination_id` is
+    /// computed on the `topology`
+    pub fn disassemble_packets_to<M: DroneSend>(
+        &mut self,
+        message: &M,
+        topology: &mut Topology,
+        source_id: NodeId,
+        destination_id: NodeId,
+        session_id: u64,
+    ) -> Result<Vec<Packet>, DisassemblerError> {
+        let routing_header = topology.get_routing_header(source_id, destination_id);
+        if routing_header.hops.is_empty() {
+            return Err(DisassemblerError::Unreachable { destination_id });
+        }
+        self.disassemble_packets(message, routing_header, session_id)
+    }
+
+    /// Same as `disassemble_packets`, using a newly allocated session id, which is returned with
+    /// the packets. Fails if the disassembler was created with `new`
+    pub fn disassemble_packets_new_session<M: DroneSend>(
+        &mut self,
+        message: &M,
+        routing_header: SourceRoutingHeader,
+    ) -> Result<(u64, Vec<Packet>), DisassemblerError> {
+        let session_id = self
+            .next_session_id()
+            .ok_or(DisassemblerError::NoSessionIdAllocator)?;
+        let packets = self.disassemble_packets(message, routing_header, session_id)?;
+        Ok((session_id, packets))
+    }
 }
@@ -0,0 +1,149 @@
+use std::marker::PhantomData;
+
+use wg_2024::packet::{Packet, PacketType};
+
+use crate::messages::general_messages::{Codec, DroneSend, DroneSendError, Message};
+
+use super::assembler::{Assembler, AssemblerConfig, AssemblerError, SessionKey};
+#[cfg(feature = "compression")]
+use super::compression::Compression;
+
+/// Reasons why a packet could not be turned into a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageAssemblerError {
+    /// The packet is not a `MsgFragment`
+    NotAFragment,
+    /// The routing header of the packet is empty, so the sender is unknown
+    UnknownSource { session_id: u64 },
+    /// The fragment was rejected by the assembler
+    Assembler(AssemblerError),
+    /// The reassembled payload is not valid UTF-8
+    InvalidUtf8 { session_id: u64, error: String },
+    /// The reassembled payload can not be deserialized into the expected message type
+    InvalidMessage {
+        session_id: u64,
+        error: DroneSendError,
+    },
+}
+
+impl std::fmt::Display for MessageAssemblerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageAssemblerError::NotAFragment => write!(f, "packet is not a fragment"),
+            MessageAssemblerError::UnknownSource { session_id } => {
+                write!(f, "session {session_id}: packet has an empty route")
+            }
+            MessageAssemblerError::Assembler(error) => write!(f, "{error}"),
+            MessageAssemblerError::InvalidUtf8 { session_id, error } => {
+                write!(f, "session {session_id}: invalid UTF-8 payload: {error}")
+            }
+            MessageAssemblerError::InvalidMessage { session_id, error } => {
+                write!(f, "session {session_id}: invalid message: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MessageAssemblerError {}
+
+impl From<AssemblerError> for MessageAssemblerError {
+    fn from(error: AssemblerError) -> Self {
+        MessageAssemblerError::Assembler(error)
+    }
+}
+
+/// Assembler working at the packet level: it is fed with the packets received by a node and
+/// returns the typed messages once all their fragments have arrived.
+/// Messages can be encoded with any `Codec`, which is detected from the payload, and compressed
+/// or not, which is detected from the header byte (see `compression`).
+///
+/// Sessions are kept apart by source node, see `SessionKey`
+pub struct MessageAssembler<M: DroneSend> {
+    assembler: Assembler,
+    _message: PhantomData<M>,
+}
+
+impl<M: DroneSend> Default for MessageAssembler<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: DroneSend> MessageAssembler<M> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_config(AssemblerConfig::default())
+    }
+
+    #[must_use]
+    pub fn with_config(config: AssemblerConfig) -> Self {
+        #[allow(unused_mut)]
+        let mut assembler = Assembler::with_config(config);
+        // serialized messages never start with a compression header, it can always be detected
+        #[cfg(feature = "compression")]
+        assembler.set_compression(Compression::Lz);
+        MessageAssembler {
+            assembler,
+            _message: PhantomData,
+        }
+    }
+
+    /// The underlying assembler, e.g. to query the missing fragments of a session
+    #[must_use]
+    pub fn assembler(&self) -> &Assembler {
+        &self.assembler
+    }
+
+    /// The underlying assembler, e.g. to evict expired sessions
+    pub fn assembler_mut(&mut self) -> &mut Assembler {
+        &mut self.assembler
+    }
+
+    /// Add the fragment carried by the packet, returning the message if it is complete.
+    /// The `source_id` of the message is the first hop of the packet route
+    ///
+    /// # Errors
+    /// * `NotAFragment` if the packet is not a `MsgFragment`
+    /// * `UnknownSource` if the routing header of the packet is empty
+    /// * `Assembler` if the fragment is rejected by the assembler
+    /// * `InvalidUtf8`/`InvalidMessage` if the complete payload is not a valid `M`
+    pub fn handle_packet(
+        &mut self,
+        packet: &Packet,
+    ) -> Result<Option<Message<M>>, MessageAssemblerError> {
+        let PacketType::MsgFragment(fragment) = &packet.pack_type else {
+            return Err(MessageAssemblerError::NotAFragment);
+        };
+        let key = SessionKey::from_packet(packet);
+        let Some(source_id) = key.source_id else {
+            return Err(MessageAssemblerError::UnknownSource {
+                session_id: packet.session_id,
+            });
+        };
+
+        let Some(payload) = self.assembler.try_add_fragment(fragment.clone(), key)? else {
+            return Ok(None);
+        };
+
+        //JSON payloads must be text, binary ones are recognized by their prefix
+        if Codec::detect(&payload) == Codec::Json {
+            if let Err(e) = std::str::from_utf8(&payload) {
+                return Err(MessageAssemblerError::InvalidUtf8 {
+                    session_id: packet.session_id,
+                    error: e.to_string(),
+                });
+            }
+        }
+        let content =
+            M::decode(&payload).map_err(|error| MessageAssemblerError::InvalidMessage {
+                session_id: packet.session_id,
+                error,
+            })?;
+
+        Ok(Some(Message {
+            source_id,
+            session_id: packet.session_id,
+            content,
+        }))
+    }
+}
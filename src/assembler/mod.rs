@@ -1,2 +1,6 @@
 pub mod assembler;
+#[cfg(feature = "compression")]
+pub mod compression;
 pub mod disassembler;
+pub mod message_assembler;
+pub mod session_id_allocator;
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+use wg_2024::network::NodeId;
+
+/// Number of bits of the session id reserved to the counter, the remaining high bits hold the node id
+const COUNTER_BITS: u32 = 56;
+const COUNTER_MASK: u64 = (1 << COUNTER_BITS) - 1;
+
+/// Hands out session ids that can not collide with the ones of other nodes: the node id is
+/// stored in the high 8 bits, and a monotonic counter in the remaining 56 bits.
+///
+/// The allocator can be serialized, so that a node restarting can resume from its last counter
+/// instead of reusing ids that may still be in flight
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionIdAllocator {
+    node_id: NodeId,
+    next: u64,
+}
+
+impl SessionIdAllocator {
+    #[must_use]
+    pub fn new(node_id: NodeId) -> Self {
+        Self::restore(node_id, 0)
+    }
+
+    /// Resume an allocator from the counter of a previous run, see `next_counter`
+    #[must_use]
+    pub fn restore(node_id: NodeId, next_counter: u64) -> Self {
+        SessionIdAllocator {
+            node_id,
+            next: next_counter & COUNTER_MASK,
+        }
+    }
+
+    #[must_use]
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    /// The counter that will be used for the next id, to be persisted and passed to `restore`
+    #[must_use]
+    pub fn next_counter(&self) -> u64 {
+        self.next
+    }
+
+    /// Allocate a new session id. After 2^56 ids the counter wraps around
+    pub fn next_id(&mut self) -> u64 {
+        let id = (u64::from(self.node_id) << COUNTER_BITS) | self.next;
+        self.next = (self.next + 1) & COUNTER_MASK;
+        id
+    }
+
+    /// The node that allocated a session id
+    #[must_use]
+    pub fn owner(session_id: u64) -> NodeId {
+        (session_id >> COUNTER_BITS) as NodeId
+    }
+}
@@ -0,0 +1,288 @@
+//! XChaCha20-Poly1305 authenticated encryption (RFC 8439 and draft-irtf-cfrg-xchacha).
+//!
+//! The 24 bytes nonce is big enough to be picked at random for every message
+
+pub const KEY_SIZE: usize = 32;
+pub const NONCE_SIZE: usize = 24;
+pub const TAG_SIZE: usize = 16;
+
+const SIGMA: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn rounds(s: &mut [u32; 16]) {
+    for _ in 0..10 {
+        quarter_round(s, 0, 4, 8, 12);
+        quarter_round(s, 1, 5, 9, 13);
+        quarter_round(s, 2, 6, 10, 14);
+        quarter_round(s, 3, 7, 11, 15);
+        quarter_round(s, 0, 5, 10, 15);
+        quarter_round(s, 1, 6, 11, 12);
+        quarter_round(s, 2, 7, 8, 13);
+        quarter_round(s, 3, 4, 9, 14);
+    }
+}
+
+// state made of the constants, the key and the last 16 bytes (counter and nonce)
+fn initial_state(key: &[u8; KEY_SIZE], input: &[u8; 16]) -> [u32; 16] {
+    let mut s = [0u32; 16];
+    s[..4].copy_from_slice(&SIGMA);
+    for i in 0..8 {
+        s[4 + i] = le32(&key[4 * i..]);
+    }
+    for i in 0..4 {
+        s[12 + i] = le32(&input[4 * i..]);
+    }
+    s
+}
+
+/// The ChaCha20 keystream block `counter` for a 12 bytes nonce
+pub(crate) fn chacha20_block(key: &[u8; KEY_SIZE], counter: u32, nonce: &[u8; 12]) -> [u8; 64] {
+    let mut input = [0u8; 16];
+    input[..4].copy_from_slice(&counter.to_le_bytes());
+    input[4..].copy_from_slice(nonce);
+    let initial = initial_state(key, &input);
+    let mut s = initial;
+    rounds(&mut s);
+
+    let mut block = [0u8; 64];
+    for i in 0..16 {
+        block[4 * i..4 * i + 4].copy_from_slice(&s[i].wrapping_add(initial[i]).to_le_bytes());
+    }
+    block
+}
+
+/// Derive a subkey from a key and a 16 bytes nonce
+pub(crate) fn hchacha20(key: &[u8; KEY_SIZE], nonce: &[u8; 16]) -> [u8; KEY_SIZE] {
+    let mut s = initial_state(key, nonce);
+    rounds(&mut s);
+
+    let mut subkey = [0u8; KEY_SIZE];
+    for i in 0..4 {
+        subkey[4 * i..4 * i + 4].copy_from_slice(&s[i].to_le_bytes());
+        subkey[16 + 4 * i..16 + 4 * i + 4].copy_from_slice(&s[12 + i].to_le_bytes());
+    }
+    subkey
+}
+
+// xor the data with the keystream, starting from the block `counter`
+fn chacha20_xor(key: &[u8; KEY_SIZE], mut counter: u32, nonce: &[u8; 12], data: &mut [u8]) {
+    for chunk in data.chunks_mut(64) {
+        let block = chacha20_block(key, counter, nonce);
+        for (byte, k) in chunk.iter_mut().zip(block) {
+            *byte ^= k;
+        }
+        counter = counter.wrapping_add(1);
+    }
+}
+
+/// Poly1305 one-time authenticator of a message
+pub(crate) fn poly1305(key: &[u8; 32], message: &[u8]) -> [u8; TAG_SIZE] {
+    const MASK: u32 = 0x3ff_ffff;
+    let r0 = le32(&key[0..]) & 0x3ff_ffff;
+    let r1 = (le32(&key[3..]) >> 2) & 0x3ff_ff03;
+    let r2 = (le32(&key[6..]) >> 4) & 0x3ff_c0ff;
+    let r3 = (le32(&key[9..]) >> 6) & 0x3f0_3fff;
+    let r4 = (le32(&key[12..]) >> 8) & 0x00f_ffff;
+    let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+    let (mut h0, mut h1, mut h2, mut h3, mut h4) = (0u32, 0u32, 0u32, 0u32, 0u32);
+
+    for chunk in message.chunks(16) {
+        // the last partial block is padded with a 1 byte, full blocks get the high bit
+        let mut block = [0u8; 16];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let hibit = if chunk.len() == 16 {
+            1 << 24
+        } else {
+            block[chunk.len()] = 1;
+            0
+        };
+
+        h0 += le32(&block[0..]) & MASK;
+        h1 += (le32(&block[3..]) >> 2) & MASK;
+        h2 += (le32(&block[6..]) >> 4) & MASK;
+        h3 += (le32(&block[9..]) >> 6) & MASK;
+        h4 += (le32(&block[12..]) >> 8) | hibit;
+
+        let m = |a: u32, b: u32| u64::from(a) * u64::from(b);
+        let d0 = m(h0, r0) + m(h1, s4) + m(h2, s3) + m(h3, s2) + m(h4, s1);
+        let mut d1 = m(h0, r1) + m(h1, r0) + m(h2, s4) + m(h3, s3) + m(h4, s2);
+        let mut d2 = m(h0, r2) + m(h1, r1) + m(h2, r0) + m(h3, s4) + m(h4, s3);
+        let mut d3 = m(h0, r3) + m(h1, r2) + m(h2, r1) + m(h3, r0) + m(h4, s4);
+        let mut d4 = m(h0, r4) + m(h1, r3) + m(h2, r2) + m(h3, r1) + m(h4, r0);
+
+        let mut c = d0 >> 26;
+        h0 = (d0 as u32) & MASK;
+        d1 += c;
+        c = d1 >> 26;
+        h1 = (d1 as u32) & MASK;
+        d2 += c;
+        c = d2 >> 26;
+        h2 = (d2 as u32) & MASK;
+        d3 += c;
+        c = d3 >> 26;
+        h3 = (d3 as u32) & MASK;
+        d4 += c;
+        c = d4 >> 26;
+        h4 = (d4 as u32) & MASK;
+        h0 += (c as u32) * 5;
+        h1 += h0 >> 26;
+        h0 &= MASK;
+    }
+
+    // full carry of h
+    let mut c = h1 >> 26;
+    h1 &= MASK;
+    h2 += c;
+    c = h2 >> 26;
+    h2 &= MASK;
+    h3 += c;
+    c = h3 >> 26;
+    h3 &= MASK;
+    h4 += c;
+    c = h4 >> 26;
+    h4 &= MASK;
+    h0 += c * 5;
+    c = h0 >> 26;
+    h0 &= MASK;
+    h1 += c;
+
+    // g = h + 5 - 2^130, used instead of h if it is not negative
+    let mut g0 = h0 + 5;
+    c = g0 >> 26;
+    g0 &= MASK;
+    let mut g1 = h1 + c;
+    c = g1 >> 26;
+    g1 &= MASK;
+    let mut g2 = h2 + c;
+    c = g2 >> 26;
+    g2 &= MASK;
+    let mut g3 = h3 + c;
+    c = g3 >> 26;
+    g3 &= MASK;
+    let g4 = (h4 + c).wrapping_sub(1 << 26);
+
+    let select = (g4 >> 31).wrapping_sub(1);
+    h0 = (h0 & !select) | (g0 & select);
+    h1 = (h1 & !select) | (g1 & select);
+    h2 = (h2 & !select) | (g2 & select);
+    h3 = (h3 & !select) | (g3 & select);
+    h4 = (h4 & !select) | (g4 & select);
+
+    // h mod 2^128, plus the second half of the key
+    let words = [
+        h0 | (h1 << 26),
+        (h1 >> 6) | (h2 << 20),
+        (h2 >> 12) | (h3 << 14),
+        (h3 >> 18) | (h4 << 8),
+    ];
+    let mut tag = [0u8; TAG_SIZE];
+    let mut f = 0u64;
+    for i in 0..4 {
+        f = u64::from(words[i]) + u64::from(le32(&key[16 + 4 * i..])) + (f >> 32);
+        tag[4 * i..4 * i + 4].copy_from_slice(&(f as u32).to_le_bytes());
+    }
+    tag
+}
+
+// Poly1305 of the associated data and the ciphertext, as defined by RFC 8439
+fn aead_tag(key: &[u8; KEY_SIZE], nonce: &[u8; 12], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+    let mut poly_key = [0u8; 32];
+    poly_key.copy_from_slice(&chacha20_block(key, 0, nonce)[..32]);
+
+    let pad = |len: usize| vec![0u8; (16 - len % 16) % 16];
+    let mut mac_data = Vec::with_capacity(aad.len() + ciphertext.len() + 48);
+    mac_data.extend_from_slice(aad);
+    mac_data.extend(pad(aad.len()));
+    mac_data.extend_from_slice(ciphertext);
+    mac_data.extend(pad(ciphertext.len()));
+    mac_data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    mac_data.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    poly1305(&poly_key, &mac_data)
+}
+
+/// ChaCha20-Poly1305 encryption, the tag is appended to the ciphertext
+pub(crate) fn chacha20poly1305_seal(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; 12],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let mut sealed = plaintext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut sealed);
+    let tag = aead_tag(key, nonce, aad, &sealed);
+    sealed.extend_from_slice(&tag);
+    sealed
+}
+
+/// ChaCha20-Poly1305 decryption, `None` if the ciphertext or the associated data were modified
+pub(crate) fn chacha20poly1305_open(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; 12],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Option<Vec<u8>> {
+    let ciphertext_len = sealed.len().checked_sub(TAG_SIZE)?;
+    let (ciphertext, tag) = sealed.split_at(ciphertext_len);
+    let expected = aead_tag(key, nonce, aad, ciphertext);
+
+    // compare in constant time
+    let difference = expected
+        .iter()
+        .zip(tag)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if difference != 0 {
+        return None;
+    }
+
+    let mut plaintext = ciphertext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut plaintext);
+    Some(plaintext)
+}
+
+// subkey and 12 bytes nonce of XChaCha20 for a 24 bytes nonce
+fn xchacha20_params(key: &[u8; KEY_SIZE], nonce: &[u8; NONCE_SIZE]) -> ([u8; KEY_SIZE], [u8; 12]) {
+    let mut hnonce = [0u8; 16];
+    hnonce.copy_from_slice(&nonce[..16]);
+    let mut chacha_nonce = [0u8; 12];
+    chacha_nonce[4..].copy_from_slice(&nonce[16..]);
+    (hchacha20(key, &hnonce), chacha_nonce)
+}
+
+/// Encrypt and authenticate the plaintext and authenticate the associated data.
+/// The tag is appended to the returned ciphertext
+#[must_use]
+pub fn seal(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let (subkey, chacha_nonce) = xchacha20_params(key, nonce);
+    chacha20poly1305_seal(&subkey, &chacha_nonce, aad, plaintext)
+}
+
+/// Decrypt a ciphertext produced by `seal`, `None` if it fails authentication
+#[must_use]
+pub fn open(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Option<Vec<u8>> {
+    let (subkey, chacha_nonce) = xchacha20_params(key, nonce);
+    chacha20poly1305_open(&subkey, &chacha_nonce, aad, sealed)
+}
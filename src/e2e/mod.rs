@@ -0,0 +1,3 @@
+pub mod cipher;
+pub mod session_keys;
+pub mod x25519;
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use wg_2024::network::NodeId;
+
+use crate::messages::chat_messages::{ChatRequest, EncryptedPayload};
+
+use super::cipher::{self, KEY_SIZE, NONCE_SIZE};
+use super::x25519;
+
+/// Reasons why a message could not be encrypted or decrypted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum E2eError {
+    /// No key was exchanged with the peer yet
+    NoSession(NodeId),
+    /// The public key of the peer is a low order point, which would give a predictable key
+    InvalidPublicKey(NodeId),
+    /// The message was not encrypted for this node by the peer, or it was modified
+    DecryptionFailed(NodeId),
+    /// A different public key was received for a peer with an established session, it may
+    /// have been sent by the server instead of the peer
+    KeyChanged(NodeId),
+}
+
+impl std::fmt::Display for E2eError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            E2eError::NoSession(peer) => write!(f, "no key exchanged with node {peer}"),
+            E2eError::InvalidPublicKey(peer) => write!(f, "invalid public key from node {peer}"),
+            E2eError::DecryptionFailed(peer) => {
+                write!(f, "could not decrypt the message from node {peer}")
+            }
+            E2eError::KeyChanged(peer) => write!(f, "the public key of node {peer} changed"),
+        }
+    }
+}
+
+impl std::error::Error for E2eError {}
+
+/// Fingerprint of a public key, to compare out of band (e.g. in person): the key in hex, in
+/// groups of 4 digits
+#[must_use]
+pub fn fingerprint(public_key: &[u8; 32]) -> String {
+    public_key
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A session with a peer
+struct Session {
+    peer_public_key: [u8; 32],
+    key: [u8; KEY_SIZE],
+}
+
+/// End-to-end encryption keys of a chat client.
+///
+/// The client sends its public key to a peer with `ChatRequest::KeyExchange`, relayed by the
+/// server as `ChatResponse::KeyExchange`; once the public key of the peer is received and passed
+/// to `establish`, the messages for that peer can be encrypted. The shared key is derived with
+/// X25519 and HChaCha20, the messages are encrypted with XChaCha20-Poly1305, authenticating the
+/// sender and the destination so that the server can not redirect them.
+///
+/// The key exchange is not authenticated: the server relaying it could send its own public key
+/// instead of the one of the peer and read the messages. The first key received for a peer is
+/// kept, a different one is refused by `establish`, so the server can not take over an
+/// established session; to detect a key replaced from the start, the clients should compare
+/// the `fingerprint` of their keys out of band
+pub struct SessionKeys {
+    node_id: NodeId,
+    secret: [u8; 32],
+    public_key: [u8; 32],
+    sessions: HashMap<NodeId, Session>,
+}
+
+impl SessionKeys {
+    /// Create the keys of a node, with a new random key pair
+    #[must_use]
+    pub fn new(node_id: NodeId) -> Self {
+        Self::from_secret(node_id, rand::random())
+    }
+
+    /// Create the keys of a node from an existing secret key
+    #[must_use]
+    pub fn from_secret(node_id: NodeId, secret: [u8; 32]) -> Self {
+        SessionKeys {
+            node_id,
+            secret,
+            public_key: x25519::public_key(&secret),
+            sessions: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    /// The public key to send to the peers
+    #[must_use]
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public_key
+    }
+
+    /// The request sending the public key to a peer, through the chat server
+    #[must_use]
+    pub fn key_exchange_request(&self, peer: NodeId) -> ChatRequest {
+        ChatRequest::KeyExchange {
+            from: self.node_id,
+            to: peer,
+            public_key: self.public_key,
+        }
+    }
+
+    /// Fingerprint of the public key of this node
+    #[must_use]
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key)
+    }
+
+    /// Fingerprint of the public key of a peer, `None` if no key was exchanged with it
+    #[must_use]
+    pub fn peer_fingerprint(&self, peer: NodeId) -> Option<String> {
+        self.sessions
+            .get(&peer)
+            .map(|session| fingerprint(&session.peer_public_key))
+    }
+
+    /// Derive the key shared with a peer from its public key. Receiving again the same public
+    /// key is fine, to replace it the session must be removed first with `forget`
+    ///
+    /// # Errors
+    /// * `InvalidPublicKey` if the public key is a low order point
+    /// * `KeyChanged` if a different public key was already received for the peer
+    pub fn establish(&mut self, peer: NodeId, peer_public_key: &[u8; 32]) -> Result<(), E2eError> {
+        if let Some(session) = self.sessions.get(&peer) {
+            if session.peer_public_key == *peer_public_key {
+                return Ok(());
+            }
+            return Err(E2eError::KeyChanged(peer));
+        }
+
+        let shared = x25519::x25519(&self.secret, peer_public_key);
+        if shared == [0u8; 32] {
+            return Err(E2eError::InvalidPublicKey(peer));
+        }
+        self.sessions.insert(
+            peer,
+            Session {
+                peer_public_key: *peer_public_key,
+                key: cipher::hchacha20(&shared, &[0u8; 16]),
+            },
+        );
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn has_session(&self, peer: NodeId) -> bool {
+        self.sessions.contains_key(&peer)
+    }
+
+    /// Forget the key shared with a peer, e.g. when it unregisters or after verifying out of
+    /// band that it changed its key
+    pub fn forget(&mut self, peer: NodeId) {
+        self.sessions.remove(&peer);
+    }
+
+    /// Encrypt a message for a peer
+    ///
+    /// # Errors
+    /// `NoSession` if no key was exchanged with the peer
+    pub fn encrypt(&self, peer: NodeId, plaintext: &[u8]) -> Result<EncryptedPayload, E2eError> {
+        let key = &self
+            .sessions
+            .get(&peer)
+            .ok_or(E2eError::NoSession(peer))?
+            .key;
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let ciphertext = cipher::seal(key, &nonce, &[self.node_id, peer], plaintext);
+        Ok(EncryptedPayload { nonce, ciphertext })
+    }
+
+    /// Decrypt a message received from a peer
+    ///
+    /// # Errors
+    /// * `NoSession` if no key was exchanged with the peer
+    /// * `DecryptionFailed` if the message was not encrypted for this node by the peer
+    pub fn decrypt(&self, peer: NodeId, payload: &EncryptedPayload) -> Result<Vec<u8>, E2eError> {
+        let key = &self
+            .sessions
+            .get(&peer)
+            .ok_or(E2eError::NoSession(peer))?
+            .key;
+        cipher::open(
+            key,
+            &payload.nonce,
+            &[peer, self.node_id],
+            &payload.ciphertext,
+        )
+        .ok_or(E2eError::DecryptionFailed(peer))
+    }
+}
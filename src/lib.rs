@@ -1,11 +1,26 @@
 pub mod assembler;
+#[cfg(feature = "e2e")]
+pub mod e2e;
 pub mod logger;
 pub mod messages;
 pub mod topology;
 
 #[cfg(test)]
 mod tests {
-    mod topology_tests;
+    mod assembler_tests;
+    mod browser_messages_tests;
+    mod chat_messages_tests;
+    #[cfg(feature = "compression")]
+    mod compression_tests;
+    mod disassembler_tests;
+    mod download_manager_tests;
+    #[cfg(feature = "e2e")]
+    mod e2e_tests;
+    mod envelope_tests;
+    mod general_messages_tests;
+    mod message_assembler_tests;
+    mod message_store_tests;
+    pub mod topology_tests;
 }
 
 pub const TIMEOUT_TIMER_MS: u64 = 500;
@@ -1,19 +1,151 @@
-use crate::messages::general_messages::{DroneSend, Request, Response, ServerTypeResponse};
+use crate::messages::general_messages::{
+    DroneSend, ErrorResponse, MessageKind, Request, Response, ServerTypeResponse,
+};
 use serde::{Deserialize, Serialize};
 
+use super::envelope::UnsupportedMessage;
 use super::general_messages::ServerTypeRequest;
 
+/**
+ * Identifier of a file of a content server.
+ * It is serialized as a bare number, so JSON messages of the servers still using `u8` ids are
+ * understood in both directions, as long as the ids fit in a `u8` (see `as_legacy`)
+ */
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct FileId(pub u32);
+
+impl FileId {
+    /// The id for a server using `u8` ids, `None` if it is too big
+    #[must_use]
+    pub fn as_legacy(self) -> Option<u8> {
+        u8::try_from(self.0).ok()
+    }
+}
+
+impl From<u8> for FileId {
+    fn from(id: u8) -> Self {
+        FileId(u32::from(id))
+    }
+}
+
+impl From<u32> for FileId {
+    fn from(id: u32) -> Self {
+        FileId(id)
+    }
+}
+
+impl std::fmt::Display for FileId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/**
+ * Kind of a file of a content server, to choose between TextFileRequest and MediaFileRequest
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileKind {
+    Text,
+    Media,
+}
+
+/**
+ * Description of a file of a content server, as listed by FileListDetailed
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMetadata {
+    pub id: FileId,
+    pub title: String,
+    pub kind: FileKind,
+    pub mime_type: String,  // e.g. "text/plain", "image/png"
+    pub size: u64,          // Size of the content, in bytes
+    pub content_hash: u64,  // `content_hash` of the content, to check the downloaded file
+    pub last_modified: u64, // Milliseconds since the Unix epoch
+}
+
+impl FileMetadata {
+    /// Describe a file, computing its size and hash from the content
+    #[must_use]
+    pub fn for_content(
+        id: FileId,
+        title: String,
+        kind: FileKind,
+        mime_type: String,
+        content: &[u8],
+        last_modified: u64,
+    ) -> Self {
+        FileMetadata {
+            id,
+            title,
+            kind,
+            mime_type,
+            size: content.len() as u64,
+            content_hash: content_hash(content),
+            last_modified,
+        }
+    }
+}
+
+/// 64 bits FNV-1a hash of the content of a file. It detects corrupted files, not tampering
+#[must_use]
+pub fn content_hash(content: &[u8]) -> u64 {
+    content.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/**
+ * Part of a media file, response to MediaFileRangeRequest
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaFileChunk {
+    pub id: FileId,
+    pub offset: u64,
+    pub total_size: u64, // Size of the whole file
+    pub data: Vec<u8>,   // Empty if the offset is past the end of the file
+    pub chunk_hash: u64, // `content_hash` of the data
+    pub file_hash: u64,  // `content_hash` of the whole file, to check the reassembled file
+}
+
+impl MediaFileChunk {
+    /// The chunk of `content` starting at `offset`, of at most `len` bytes
+    #[must_use]
+    pub fn from_content(id: FileId, content: &[u8], offset: u64, len: u64) -> Self {
+        let start = usize::try_from(offset).map_or(content.len(), |o| o.min(content.len()));
+        let end = usize::try_from(len).map_or(content.len(), |l| {
+            start.saturating_add(l).min(content.len())
+        });
+        let data = content[start..end].to_vec();
+        MediaFileChunk {
+            id,
+            offset,
+            total_size: content.len() as u64,
+            chunk_hash: content_hash(&data),
+            data,
+            file_hash: content_hash(content),
+        }
+    }
+}
+
 /**
  * Request type for a chat client
  */
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BrowserRequest {
     FileList,
-    TextFileRequest(u8),
-    MediaFileRequest(u8),
+    FileListDetailed, // Same as FileList, with the metadata of the files
+    TextFileRequest(FileId),
+    MediaFileRequest(FileId),
+    MediaFileRangeRequest { id: FileId, offset: u64, len: u64 }, // Request part of a media file, see download_manager::DownloadManager
 }
 
 impl DroneSend for BrowserRequest {}
+impl MessageKind for BrowserRequest {
+    const KIND: &'static str = "BrowserRequest";
+}
 impl Request for BrowserRequest {}
 
 /**
@@ -21,22 +153,32 @@ impl Request for BrowserRequest {}
  */
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BrowserResponse {
-    FileList(Vec<u8>),
-    TextFile(u8, String),
-    MediaFile(u8, Vec<u8>),
+    FileList(Vec<FileId>),
+    FileListDetailed(Vec<FileMetadata>),
+    TextFile(FileId, String),
+    MediaFile(FileId, Vec<u8>),
+    MediaFileRange(MediaFileChunk),
 }
 
 impl DroneSend for BrowserResponse {}
+impl MessageKind for BrowserResponse {
+    const KIND: &'static str = "BrowserResponse";
+}
 impl Response for BrowserResponse {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BrowserResponseWrapper {
     Chat(BrowserResponse),
     ServerType(ServerTypeResponse),
+    Unsupported(UnsupportedMessage), // The request could not be handled by the server
+    Error(ErrorResponse),            // The request failed
 }
 
 impl Response for BrowserResponseWrapper {}
 impl DroneSend for BrowserResponseWrapper {}
+impl MessageKind for BrowserResponseWrapper {
+    const KIND: &'static str = "BrowserResponseWrapper";
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BrowserRequestWrapper {
@@ -46,3 +188,6 @@ pub enum BrowserRequestWrapper {
 
 impl Request for BrowserRequestWrapper {}
 impl DroneSend for BrowserRequestWrapper {}
+impl MessageKind for BrowserRequestWrapper {
+    const KIND: &'static str = "BrowserRequestWrapper";
+}
@@ -1,9 +1,204 @@
-use crate::messages::general_messages::{DroneSend, Request, Response, ServerTypeResponse};
-use serde::{Deserialize, Serialize};
+use crate::messages::general_messages::{
+    DroneSend, ErrorResponse, MessageKind, Request, Response, ServerTypeResponse,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 use wg_2024::network::NodeId;
 
+use super::browser_messages::FileId;
+use super::envelope::UnsupportedMessage;
 use super::general_messages::ServerTypeRequest;
 
+/// Identifier of a chat channel (group conversation), assigned by the chat server
+pub type ChannelId = u64;
+
+/// Identifier of a chat message, chosen by the sender and unique among its messages.
+/// Receipts refer to the message by this id
+pub type MessageId = u64;
+
+/**
+ * Description of a channel, as listed by the chat server
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelInfo {
+    pub id: ChannelId,
+    pub name: String,
+    pub members: Vec<NodeId>,
+}
+
+/// Maximum size of the data of an attachment, bigger files are only referenced by their media id
+pub const MAX_ATTACHMENT_SIZE: usize = 16 * 1024;
+
+/// Reasons why a `ChatContent` is refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatContentError {
+    /// The data of the attachment is bigger than `MAX_ATTACHMENT_SIZE`
+    AttachmentTooLarge { size: usize, max: usize },
+}
+
+impl std::fmt::Display for ChatContentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChatContentError::AttachmentTooLarge { size, max } => {
+                write!(f, "attachment of {size} bytes exceeds the maximum of {max}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChatContentError {}
+
+/**
+ * Content of a chat message
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatContent {
+    Text(String),
+    Reply {
+        reply_to: MessageId,
+        text: String,
+    }, // Answer to a previous message of the conversation
+    Edit {
+        message_id: MessageId,
+        text: String,
+    }, // Replace the text of a message previously sent by the same client
+    Delete(MessageId), // Delete a message previously sent by the same client
+    Attachment {
+        server_id: NodeId,
+        media_id: FileId,
+        data: Vec<u8>,
+    }, // Media file of a content server (BrowserRequest::MediaFile), with a preview of at most MAX_ATTACHMENT_SIZE bytes
+}
+
+impl ChatContent {
+    /// An attachment, checking the size of its data
+    ///
+    /// # Errors
+    /// `AttachmentTooLarge` if the data is bigger than `MAX_ATTACHMENT_SIZE`
+    pub fn attachment(
+        server_id: NodeId,
+        media_id: FileId,
+        data: Vec<u8>,
+    ) -> Result<Self, ChatContentError> {
+        let content = ChatContent::Attachment {
+            server_id,
+            media_id,
+            data,
+        };
+        content.validate()?;
+        Ok(content)
+    }
+
+    /// Check the content received from a client, before relaying it
+    ///
+    /// # Errors
+    /// `AttachmentTooLarge` if the data of an attachment is bigger than `MAX_ATTACHMENT_SIZE`
+    pub fn validate(&self) -> Result<(), ChatContentError> {
+        match self {
+            ChatContent::Attachment { data, .. } if data.len() > MAX_ATTACHMENT_SIZE => {
+                Err(ChatContentError::AttachmentTooLarge {
+                    size: data.len(),
+                    max: MAX_ATTACHMENT_SIZE,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The text of the message, `None` for deletions and attachments
+    #[must_use]
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            ChatContent::Text(text)
+            | ChatContent::Reply { text, .. }
+            | ChatContent::Edit { text, .. } => Some(text),
+            ChatContent::Delete(_) | ChatContent::Attachment { .. } => None,
+        }
+    }
+}
+
+impl From<String> for ChatContent {
+    fn from(text: String) -> Self {
+        ChatContent::Text(text)
+    }
+}
+
+impl From<&str> for ChatContent {
+    fn from(text: &str) -> Self {
+        ChatContent::Text(text.to_string())
+    }
+}
+
+// Accepts the messages of the clients sending the text as a bare string (SendMessage,
+// SendChannelMessage) or as bytes (MessageFrom, ChannelMessageFrom).
+// Only JSON can carry them, other codecs always use ChatContent
+fn deserialize_content<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ChatContent, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum LegacyContent {
+        Content(ChatContent),
+        Text(String),
+        Bytes(Vec<u8>),
+    }
+
+    if !deserializer.is_human_readable() {
+        return ChatContent::deserialize(deserializer);
+    }
+    Ok(match LegacyContent::deserialize(deserializer)? {
+        LegacyContent::Content(content) => content,
+        LegacyContent::Text(text) => ChatContent::Text(text),
+        LegacyContent::Bytes(bytes) => {
+            ChatContent::Text(String::from_utf8_lossy(&bytes).into_owned())
+        }
+    })
+}
+
+/**
+ * A message kept by the chat server, returned in the history and in the queued messages
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub message_id: Option<MessageId>, // None if the sender predates receipts
+    pub from: NodeId,
+    pub to: NodeId,
+    pub timestamp: u64, // Milliseconds since the Unix epoch, when the server received the message
+    pub message: ChatContent,
+}
+
+impl StoredMessage {
+    /// Create a message received now
+    #[must_use]
+    pub fn new(
+        message_id: Option<MessageId>,
+        from: NodeId,
+        to: NodeId,
+        message: ChatContent,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        StoredMessage {
+            message_id,
+            from,
+            to,
+            timestamp,
+            message,
+        }
+    }
+}
+
+/**
+ * A message encrypted for its destination, only the two clients can read it.
+ * With the `e2e` feature it is created and read by `e2e::session_keys::SessionKeys`
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptedPayload {
+    pub nonce: [u8; 24],
+    pub ciphertext: Vec<u8>, // The encrypted message followed by the authentication tag
+}
+
 /**
  * Request type for a chat client
  */
@@ -11,14 +206,66 @@ use super::general_messages::ServerTypeRequest;
 pub enum ChatRequest {
     ClientList,
     Register(NodeId),
+    Unregister(NodeId), // Remove the client from the server, e.g. before shutting down
     SendMessage {
         from: NodeId,
         to: NodeId,
-        message: String,
+        #[serde(deserialize_with = "deserialize_content")]
+        message: ChatContent,
+        #[serde(default)]
+        message_id: Option<MessageId>, // None for clients that predate receipts, no receipt is sent back
     },
+    Delivered {
+        from: NodeId,
+        to: NodeId,
+        message_id: MessageId,
+    }, // Sent by the destination of a message when it is received, relayed to the sender (to)
+    Read {
+        from: NodeId,
+        to: NodeId,
+        message_id: MessageId,
+    }, // Sent by the destination of a message when it is read, relayed to the sender (to)
+    KeyExchange {
+        from: NodeId,
+        to: NodeId,
+        public_key: [u8; 32],
+    }, // Send the public key of the client to another client, relayed by the server
+    SendEncryptedMessage {
+        from: NodeId,
+        to: NodeId,
+        payload: EncryptedPayload,
+        message_id: MessageId,
+    }, // Same as SendMessage, the server relays the payload without reading it
+    History {
+        with: NodeId,
+        since: Option<u64>,
+        limit: usize,
+    }, // Get the last `limit` messages exchanged with a client, sent after the `since` timestamp
+    CreateChannel {
+        from: NodeId,
+        name: String,
+    }, // Create a channel, the creator joins it
+    JoinChannel {
+        from: NodeId,
+        channel_id: ChannelId,
+    },
+    LeaveChannel {
+        from: NodeId,
+        channel_id: ChannelId,
+    },
+    ChannelList, // Get the list of channels of the server
+    SendChannelMessage {
+        from: NodeId,
+        channel_id: ChannelId,
+        #[serde(deserialize_with = "deserialize_content")]
+        message: ChatContent,
+    }, // Send a message to every member of a channel
 }
 
 impl DroneSend for ChatRequest {}
+impl MessageKind for ChatRequest {
+    const KIND: &'static str = "ChatRequest";
+}
 impl Request for ChatRequest {}
 
 /**
@@ -27,22 +274,58 @@ impl Request for ChatRequest {}
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChatResponse {
     ClientList(Vec<NodeId>), // Response to a client when the list of clients is requested
-    MessageFrom { from: NodeId, message: Vec<u8> }, // Response to a client when a message is received
-    MessageSent,      // Response to a client when the message is sent successfully
-    ClientRegistered, // Response to a client when the registration is successful
+    MessageFrom {
+        from: NodeId,
+        #[serde(deserialize_with = "deserialize_content")]
+        message: ChatContent,
+        #[serde(default)]
+        message_id: Option<MessageId>, // None if the sender predates receipts, no receipt must be sent
+    }, // Response to a client when a message is received
+    EncryptedMessageFrom {
+        from: NodeId,
+        payload: EncryptedPayload,
+        message_id: MessageId,
+    }, // Response to a client when an encrypted message is received
+    KeyExchange(NodeId, [u8; 32]), // Relayed to a client when another client sends its public key (from, public_key)
+    MessageSent, // Response to a client when the message is accepted by the server
+    Delivered(NodeId, MessageId), // Relayed to the sender when the destination received the message (destination, message_id)
+    Read(NodeId, MessageId), // Relayed to the sender when the destination read the message (destination, message_id)
+    ClientRegistered,        // Response to a client when the registration is successful
+    QueuedMessages(Vec<StoredMessage>), // Sent after the registration, the messages received while the client was unreachable
+    History(NodeId, Vec<StoredMessage>), // Response to a history request (with, messages from the oldest)
+    ClientUnregistered,                  // Response to a client when it is removed from the server
+    ClientJoined(NodeId), // Pushed to the registered clients when a new client registers
+    ClientLeft(NodeId),   // Pushed to the registered clients when a client unregisters
+    ChannelCreated(ChannelId), // Response to a client when the channel is created
+    ChannelJoined(ChannelId), // Response to a client when it joins a channel
+    ChannelLeft(ChannelId), // Response to a client when it leaves a channel
+    ChannelList(Vec<ChannelInfo>), // Response to a client when the list of channels is requested
+    ChannelMessageFrom(
+        ChannelId,
+        NodeId,
+        #[serde(deserialize_with = "deserialize_content")] ChatContent,
+    ), // Sent to every member of a channel when a message is sent to it (channel_id, from, message)
 }
 
 impl DroneSend for ChatResponse {}
+impl MessageKind for ChatResponse {
+    const KIND: &'static str = "ChatResponse";
+}
 impl Response for ChatResponse {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChatResponseWrapper {
     Chat(ChatResponse),
     ServerType(ServerTypeResponse),
+    Unsupported(UnsupportedMessage), // The request could not be handled by the server
+    Error(ErrorResponse),            // The request failed
 }
 
 impl Response for ChatResponseWrapper {}
 impl DroneSend for ChatResponseWrapper {}
+impl MessageKind for ChatResponseWrapper {
+    const KIND: &'static str = "ChatResponseWrapper";
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ChatRequestWrapper {
@@ -52,3 +335,6 @@ pub enum ChatRequestWrapper {
 
 impl Request for ChatRequestWrapper {}
 impl DroneSend for ChatRequestWrapper {}
+impl MessageKind for ChatRequestWrapper {
+    const KIND: &'static str = "ChatRequestWrapper";
+}
@@ -5,11 +5,13 @@ use serde::{Deserialize, Serialize};
 use wg_2024::{network::NodeId, packet::Packet};
 
 use crate::{
-    messages::general_messages::{DroneSend, Request},
+    messages::general_messages::{DroneSend, MessageKind, Request},
     topology::Topology,
 };
 
-use super::general_messages::ServerType;
+use super::browser_messages::{FileId, FileMetadata};
+use super::chat_messages::{ChannelId, ChannelInfo, ChatContent, MessageId, StoredMessage};
+use super::general_messages::{ErrorResponse, ServerType};
 
 /**
  * Command that can be sent from the simulation controller to the (chat) clients
@@ -18,18 +20,29 @@ use super::general_messages::ServerType;
 pub enum SimControllerCommand {
     SendMessage(String, NodeId, NodeId), // Send message to a server, the first id is the server, the second the destination client
     Register(NodeId),                    // Register a client to a server
+    Unregister(NodeId),                  // Unregister a client from a server
     ClientList(NodeId),                  // Get the list of available clients from a server
     FloodRequest,                        // Send a flood request
     Topology,                            // Get the topology of the network
-    RequestTextFile(u8, NodeId), // Request a text file from the server (filename, server_id)
-    RequestMediaFile(u8, NodeId), // Request a media file from the server (filename, server_id)
-    RequestFileList(NodeId),     // Request the list of available files from the server
-    KnownServers,                // Request the client its list of known servers
-    RegisteredServers,           // Request the list of servers to which the client is registered
-    RemoveSender(NodeId),        // Remove a sender from the list of neighbors
+    RequestTextFile(FileId, NodeId), // Request a text file from the server (filename, server_id)
+    RequestMediaFile(FileId, NodeId), // Request a media file from the server (filename, server_id)
+    RequestFileList(NodeId),         // Request the list of available files from the server
+    RequestFileListDetailed(NodeId), // Request the list of available files, with their metadata, from the server
+    KnownServers,                    // Request the client its list of known servers
+    RegisteredServers, // Request the list of servers to which the client is registered
+    RemoveSender(NodeId), // Remove a sender from the list of neighbors
     AddSender(NodeId, Sender<Packet>), // Add a sender to the list of neighbors
-    RequestServerType(NodeId),   // Request the type of a server
-    Shutdown,                    // Shutdown the client/server
+    RequestServerType(NodeId), // Request the type of a server
+    Shutdown,          // Shutdown the client/server
+    CreateChannel(String, NodeId), // Create a channel on a server (channel name, server_id)
+    JoinChannel(ChannelId, NodeId), // Join a channel of a server (channel_id, server_id)
+    LeaveChannel(ChannelId, NodeId), // Leave a channel of a server (channel_id, server_id)
+    ChannelList(NodeId), // Get the list of channels of a server
+    SendChannelMessage(String, ChannelId, NodeId), // Send message to a channel (message, channel_id, server_id)
+    KeyExchange(NodeId, NodeId), // Send the public key to a client, to encrypt the messages (node_to, server_id)
+    SendEncryptedMessage(String, NodeId, NodeId), // Same as SendMessage, the message is end-to-end encrypted
+    History(NodeId, NodeId), // Get the messages exchanged with a client (node_with, server_id)
+    MarkRead(MessageId, NodeId, NodeId), // Send the read receipt of a received message (message_id, server_id, node_from)
 }
 
 /**
@@ -37,25 +50,38 @@ pub enum SimControllerCommand {
  */
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SimControllerMessage {
-    FloodResponse(u64),                        // Response to a flood request
-    TopologyResponse(Topology),                // Response to a topology request
+    FloodResponse(u64),                                  // Response to a flood request
+    TopologyResponse(Topology),                          // Response to a topology request
     ClientListResponse(NodeId, Vec<NodeId>), // The client list associated to a server, as the client knows it
-    MessageReceived(NodeId, NodeId, String), // A message received by a client (server_id, node_from, message)
-    TextFileResponse(u8, String),            // Response to a text file request
-    MediaFileResponse(u8, Vec<u8>),          // Response to a media file request
-    FileListResponse(NodeId, Vec<u8>), // Response  a file list request the NodeId refers to the server generating the response
+    MessageReceived(NodeId, NodeId, ChatContent), // A message received by a client (server_id, node_from, message)
+    TextFileResponse(FileId, String),             // Response to a text file request
+    MediaFileResponse(FileId, Vec<u8>),           // Response to a media file request
+    FileListResponse(NodeId, Vec<FileId>), // Response  a file list request the NodeId refers to the server generating the response
+    FileListDetailedResponse(NodeId, Vec<FileMetadata>), // Response to a detailed file list request (server_id, files)
     ServerTypeResponse(NodeId, ServerType), // Response to ServerType request from a client
     KnownServers(HashMap<NodeId, ServerType>), // Response to KnownServers request from a client
-    RegisteredServersResponse(Vec<u8>), // Response to a list of registered servers
-    TextWithReferences(u8, String, HashMap<u8, Vec<u8>>), // File Id, Text, References
+    RegisteredServersResponse(Vec<u8>),     // Response to a list of registered servers
+    TextWithReferences(FileId, String, HashMap<FileId, Vec<u8>>), // File Id, Text, References
+    ErrorResponse(NodeId, ErrorResponse),   // Error returned by a server (server_id, error)
+    ChannelCreated(NodeId, ChannelId),      // A channel created by a client (server_id, channel_id)
+    ChannelListResponse(NodeId, Vec<ChannelInfo>), // The channels of a server, as the client knows them
+    ChannelMessageReceived(NodeId, ChannelId, NodeId, ChatContent), // A message received in a channel (server_id, channel_id, node_from, message)
+    HistoryResponse(NodeId, NodeId, Vec<StoredMessage>), // The messages exchanged with a client (server_id, node_with, messages)
+    QueuedMessagesReceived(NodeId, Vec<StoredMessage>), // The messages received while the client was unreachable (server_id, messages)
+    MessageDelivered(NodeId, NodeId, MessageId), // A sent message was received by its destination (server_id, node_to, message_id)
+    MessageRead(NodeId, NodeId, MessageId), // A sent message was read by its destination (server_id, node_to, message_id)
 }
 
 impl DroneSend for SimControllerMessage {}
+impl MessageKind for SimControllerMessage {
+    const KIND: &'static str = "SimControllerMessage";
+}
 impl Request for SimControllerMessage {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SimControllerEvent {
     ChatMessageSent(NodeId, NodeId, String), // A message sent by a client (server_id, node_to, message)
+    ChatMessageSentWithId(NodeId, NodeId, MessageId), // A message sent by a client that will be acknowledged with receipts (server_id, node_to, message_id)
     FloodRequestSent,
     MessageSent {
         session_id: u64,
@@ -69,6 +95,9 @@ pub enum SimControllerEvent {
 }
 
 impl DroneSend for SimControllerEvent {}
+impl MessageKind for SimControllerEvent {
+    const KIND: &'static str = "SimControllerEvent";
+}
 impl Request for SimControllerEvent {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,3 +107,6 @@ pub enum SimControllerResponseWrapper {
 }
 
 impl DroneSend for SimControllerResponseWrapper {}
+impl MessageKind for SimControllerResponseWrapper {
+    const KIND: &'static str = "SimControllerResponseWrapper";
+}
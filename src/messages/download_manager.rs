@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use wg_2024::network::NodeId;
+
+use crate::TIMEOUT_TIMER_MS;
+
+use super::browser_messages::{content_hash, BrowserRequest, FileId, MediaFileChunk};
+
+/// Reasons why a chunk was not added to its download. The download goes on: the chunk, or the
+/// whole file, is requested again by `next_requests`, except after `UnknownDownload`,
+/// `FileTooLarge` and `EmptyChunk`, which end it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadError {
+    /// The file is not being downloaded from the server, e.g. the download completed or was cancelled
+    UnknownDownload { server_id: NodeId, id: FileId },
+    /// The chunk overlaps data already received, or starts past the end of the file
+    UnexpectedChunk { id: FileId, offset: u64 },
+    /// The data of the chunk does not match its hash
+    ChunkChecksumMismatch { id: FileId, offset: u64 },
+    /// The chunk is longer than requested, or goes past the end of the file
+    InvalidChunkLength {
+        id: FileId,
+        offset: u64,
+        expected: u64,
+        received: u64,
+    },
+    /// The file is bigger than `max_file_size`, the download is cancelled
+    FileTooLarge { id: FileId, size: u64, max: u64 },
+    /// The server sent no data before the end of the file, the download is cancelled
+    EmptyChunk { id: FileId, offset: u64 },
+    /// The file changed on the server during the download, it is downloaded again from the start
+    FileChanged { id: FileId },
+    /// The reassembled file does not match its hash, it is downloaded again from the start
+    FileChecksumMismatch { id: FileId },
+}
+
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::UnknownDownload { server_id, id } => {
+                write!(f, "file {id} is not being downloaded from node {server_id}")
+            }
+            DownloadError::UnexpectedChunk { id, offset } => {
+                write!(f, "file {id}: unexpected chunk at offset {offset}")
+            }
+            DownloadError::ChunkChecksumMismatch { id, offset } => {
+                write!(f, "file {id}: corrupted chunk at offset {offset}")
+            }
+            DownloadError::InvalidChunkLength {
+                id,
+                offset,
+                expected,
+                received,
+            } => write!(
+                f,
+                "file {id}: chunk at offset {offset} has {received} bytes, expected {expected}"
+            ),
+            DownloadError::FileTooLarge { id, size, max } => {
+                write!(
+                    f,
+                    "file {id} has {size} bytes, more than the limit of {max}"
+                )
+            }
+            DownloadError::EmptyChunk { id, offset } => {
+                write!(f, "file {id}: empty chunk at offset {offset}")
+            }
+            DownloadError::FileChanged { id } => write!(f, "file {id} changed on the server"),
+            DownloadError::FileChecksumMismatch { id } => write!(f, "file {id} is corrupted"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+/// Settings of a `DownloadManager`
+///
+/// * `chunk_size: u64` - bytes requested with each `MediaFileRangeRequest`
+/// * `parallel_chunks: usize` - maximum number of chunks of a file requested at the same time
+/// * `chunk_timeout: Duration` - how long to wait for a chunk before requesting it again
+/// * `max_file_size: u64` - bigger files are not downloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadConfig {
+    pub chunk_size: u64,
+    pub parallel_chunks: usize,
+    pub chunk_timeout: Duration,
+    pub max_file_size: u64,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            chunk_size: 32 * 1024,
+            parallel_chunks: 4,
+            chunk_timeout: Duration::from_millis(TIMEOUT_TIMER_MS),
+            max_file_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// A file being downloaded
+#[derive(Default)]
+struct Download {
+    // size and hash of the file, known after the first chunk is received
+    total_size: Option<u64>,
+    file_hash: Option<u64>,
+    // chunks received, by offset
+    received: BTreeMap<u64, Vec<u8>>,
+    // chunks requested and not received yet, by offset, with their length and the time of the request
+    in_flight: HashMap<u64, (u64, Instant)>,
+}
+
+impl Download {
+    fn received_bytes(&self) -> u64 {
+        self.received.values().map(|data| data.len() as u64).sum()
+    }
+
+    // offset and length of the chunks neither received nor requested, in order, only the first
+    // one if the size is unknown. The chunks are built lazily, so only the ones taken are counted
+    fn missing(&self, chunk_size: u64) -> impl Iterator<Item = (u64, u64)> + '_ {
+        let end = self.total_size.unwrap_or(chunk_size);
+        let mut covered: Vec<(u64, u64)> = self
+            .received
+            .iter()
+            .map(|(&offset, data)| (offset, offset + data.len() as u64))
+            .chain(
+                self.in_flight
+                    .iter()
+                    .map(|(&offset, &(len, _))| (offset, offset.saturating_add(len))),
+            )
+            .collect();
+        covered.sort_unstable();
+
+        let mut gaps = Vec::new();
+        let mut cursor = 0;
+        for (start, stop) in covered {
+            if start > cursor {
+                gaps.push((cursor, start.min(end)));
+            }
+            cursor = cursor.max(stop);
+        }
+        if cursor < end {
+            gaps.push((cursor, end));
+        }
+
+        let step = usize::try_from(chunk_size).unwrap_or(usize::MAX);
+        gaps.into_iter()
+            .filter(|(start, stop)| start < stop)
+            .flat_map(move |(start, stop)| {
+                (start..stop)
+                    .step_by(step)
+                    .map(move |offset| (offset, chunk_size.min(stop - offset)))
+            })
+    }
+}
+
+/// Downloads media files from content servers in chunks, with `MediaFileRangeRequest`.
+///
+/// Up to `parallel_chunks` chunks of each file are requested at the same time, every request
+/// being sent in its own session. The chunks are checked against their hash, and the whole
+/// file against the hash of the file: corrupted chunks and chunks not received within the
+/// timeout are requested again by `next_requests`, so a lost fragment only costs one chunk.
+/// A server may send less than requested, e.g. if it limits the length of a range: the rest
+/// of the range is requested again
+pub struct DownloadManager {
+    config: DownloadConfig,
+    downloads: HashMap<(NodeId, FileId), Download>,
+}
+
+impl Default for DownloadManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DownloadManager {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_config(DownloadConfig::default())
+    }
+
+    #[must_use]
+    pub fn with_config(config: DownloadConfig) -> Self {
+        DownloadManager {
+            config: DownloadConfig {
+                chunk_size: config.chunk_size.max(1),
+                parallel_chunks: config.parallel_chunks.max(1),
+                ..config
+            },
+            downloads: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn config(&self) -> DownloadConfig {
+        self.config
+    }
+
+    /// Start downloading a file from a server, the requests are returned by `next_requests`.
+    /// Returns false if the file is already being downloaded
+    pub fn start(&mut self, server_id: NodeId, id: FileId) -> bool {
+        if self.downloads.contains_key(&(server_id, id)) {
+            return false;
+        }
+        self.downloads.insert((server_id, id), Download::default());
+        true
+    }
+
+    /// Stop downloading a file, dropping the chunks received so far
+    pub fn cancel(&mut self, server_id: NodeId, id: FileId) -> bool {
+        self.downloads.remove(&(server_id, id)).is_some()
+    }
+
+    /// The requests to send now, with the server to send them to: the chunks not requested yet,
+    /// and the ones not received within the timeout
+    pub fn next_requests(&mut self, now: Instant) -> Vec<(NodeId, BrowserRequest)> {
+        let config = self.config;
+        let mut requests = Vec::new();
+        for (&(server_id, id), download) in &mut self.downloads {
+            download.in_flight.retain(|_, (_, sent_at)| {
+                now.saturating_duration_since(*sent_at) < config.chunk_timeout
+            });
+
+            let free = config
+                .parallel_chunks
+                .saturating_sub(download.in_flight.len());
+            let missing: Vec<(u64, u64)> = download.missing(config.chunk_size).take(free).collect();
+            for (offset, len) in missing {
+                download.in_flight.insert(offset, (len, now));
+                requests.push((
+                    server_id,
+                    BrowserRequest::MediaFileRangeRequest { id, offset, len },
+                ));
+            }
+        }
+        requests
+    }
+
+    /// Add a chunk received from a server, returning the whole file once all the chunks are
+    /// received. Chunks received twice are ignored
+    ///
+    /// # Errors
+    /// See `DownloadError`, the download goes on after every error but `UnknownDownload`,
+    /// `FileTooLarge` and `EmptyChunk`
+    pub fn handle_chunk(
+        &mut self,
+        server_id: NodeId,
+        chunk: MediaFileChunk,
+    ) -> Result<Option<Vec<u8>>, DownloadError> {
+        let chunk_size = self.config.chunk_size;
+        let id = chunk.id;
+        let offset = chunk.offset;
+        let download = self
+            .downloads
+            .get_mut(&(server_id, id))
+            .ok_or(DownloadError::UnknownDownload { server_id, id })?;
+
+        if download.received.contains_key(&offset) {
+            return Ok(None);
+        }
+        if content_hash(&chunk.data) != chunk.chunk_hash {
+            download.in_flight.remove(&offset);
+            return Err(DownloadError::ChunkChecksumMismatch { id, offset });
+        }
+        if chunk.total_size > self.config.max_file_size {
+            self.downloads.remove(&(server_id, id));
+            return Err(DownloadError::FileTooLarge {
+                id,
+                size: chunk.total_size,
+                max: self.config.max_file_size,
+            });
+        }
+
+        match (download.total_size, download.file_hash) {
+            (Some(total_size), Some(file_hash))
+                if (total_size, file_hash) != (chunk.total_size, chunk.file_hash) =>
+            {
+                *download = Download::default();
+                return Err(DownloadError::FileChanged { id });
+            }
+            _ => {
+                download.total_size = Some(chunk.total_size);
+                download.file_hash = Some(chunk.file_hash);
+            }
+        }
+
+        if offset > 0 && offset >= chunk.total_size {
+            return Err(DownloadError::UnexpectedChunk { id, offset });
+        }
+        let requested = download
+            .in_flight
+            .remove(&offset)
+            .map_or(chunk_size, |(len, _)| len);
+        let expected = requested.min(chunk.total_size - offset);
+        let received = chunk.data.len() as u64;
+        if received > expected {
+            return Err(DownloadError::InvalidChunkLength {
+                id,
+                offset,
+                expected,
+                received,
+            });
+        }
+        if received == 0 && expected > 0 {
+            self.downloads.remove(&(server_id, id));
+            return Err(DownloadError::EmptyChunk { id, offset });
+        }
+        let overlaps_previous = download
+            .received
+            .range(..offset)
+            .next_back()
+            .is_some_and(|(&start, data)| start + data.len() as u64 > offset);
+        let overlaps_next = download
+            .received
+            .range(offset..)
+            .next()
+            .is_some_and(|(&start, _)| start < offset + received);
+        if overlaps_previous || overlaps_next {
+            return Err(DownloadError::UnexpectedChunk { id, offset });
+        }
+        download.received.insert(offset, chunk.data);
+
+        if download.received_bytes() < chunk.total_size {
+            return Ok(None);
+        }
+        let file: Vec<u8> = std::mem::take(&mut download.received)
+            .into_values()
+            .flatten()
+            .collect();
+        if content_hash(&file) != chunk.file_hash {
+            *download = Download::default();
+            return Err(DownloadError::FileChecksumMismatch { id });
+        }
+        self.downloads.remove(&(server_id, id));
+        Ok(Some(file))
+    }
+
+    /// Bytes received and size of a file, `None` if it is not being downloaded.
+    /// The size is known once the first chunk is received
+    #[must_use]
+    pub fn progress(&self, server_id: NodeId, id: FileId) -> Option<(u64, Option<u64>)> {
+        self.downloads
+            .get(&(server_id, id))
+            .map(|download| (download.received_bytes(), download.total_size))
+    }
+
+    /// Number of files being downloaded
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.downloads.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.downloads.is_empty()
+    }
+}
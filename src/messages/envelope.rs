@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+
+use super::general_messages::{Codec, DroneSend, DroneSendError, MessageKind, Response};
+
+/// Version of the protocol spoken by this version of the library
+pub const PROTOCOL_VERSION: u16 = 1;
+/// Version assigned to messages sent without an envelope, by nodes that predate versioning
+pub const LEGACY_PROTOCOL_VERSION: u16 = 0;
+
+/**
+ * Range of protocol versions supported by a node, exchanged with `ServerTypeRequest::ProtocolVersion`
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolVersionRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+impl Default for ProtocolVersionRange {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+impl ProtocolVersionRange {
+    /// The versions supported by this version of the library
+    #[must_use]
+    pub fn current() -> Self {
+        ProtocolVersionRange {
+            min: LEGACY_PROTOCOL_VERSION,
+            max: PROTOCOL_VERSION,
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, version: u16) -> bool {
+        (self.min..=self.max).contains(&version)
+    }
+
+    /// The highest version supported by both ranges, `None` if they do not overlap
+    #[must_use]
+    pub fn negotiate(&self, other: &ProtocolVersionRange) -> Option<u16> {
+        let max = self.max.min(other.max);
+        (max >= self.min.max(other.min)).then_some(max)
+    }
+}
+
+/**
+ * Versioned envelope around a message: the receiver can check the protocol version and the kind
+ * of message before trying to deserialize the payload, and answer with an `UnsupportedMessage`
+ * instead of failing to parse it.
+ *
+ * The `correlation_id` of a request is echoed back in the envelope of its response, so a client
+ * with many outstanding requests knows which one is answered, see `PendingRequests`
+ */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope<M> {
+    pub version: u16,
+    pub kind: String,
+    #[serde(default)]
+    pub correlation_id: Option<u64>,
+    pub payload: M,
+}
+
+impl<M: DroneSend> DroneSend for Envelope<M> {}
+impl<M> MessageKind for Envelope<M> {
+    const KIND: &'static str = "Envelope";
+}
+
+/// The fields in front of every envelope, read before the payload
+#[derive(Deserialize)]
+struct EnvelopeHeader {
+    version: u16,
+    kind: String,
+    #[serde(default)]
+    correlation_id: Option<u64>,
+}
+
+impl<M: DroneSend + MessageKind> Envelope<M> {
+    /// Wrap a message in an envelope of the current protocol version
+    #[must_use]
+    pub fn new(payload: M) -> Self {
+        Envelope {
+            version: PROTOCOL_VERSION,
+            kind: M::KIND.to_string(),
+            correlation_id: None,
+            payload,
+        }
+    }
+
+    /// Wrap a request in an envelope, with the id its response will echo
+    #[must_use]
+    pub fn request(payload: M, correlation_id: u64) -> Self {
+        Envelope {
+            correlation_id: Some(correlation_id),
+            ..Envelope::new(payload)
+        }
+    }
+
+    /// Wrap the response to this envelope, echoing its correlation id
+    #[must_use]
+    pub fn reply<R: DroneSend + MessageKind>(&self, payload: R) -> Envelope<R> {
+        Envelope {
+            correlation_id: self.correlation_id,
+            ..Envelope::new(payload)
+        }
+    }
+
+    /// Wrap a message in an envelope and serialize it with the given codec
+    pub fn seal(payload: M, codec: Codec) -> Result<Vec<u8>, DroneSendError> {
+        Envelope::new(payload).encode(codec)
+    }
+
+    /// Deserialize an envelope (with any codec), accepting the versions of `supported`.
+    /// Messages sent without an envelope by older nodes are accepted as `LEGACY_PROTOCOL_VERSION`.
+    ///
+    /// # Errors
+    /// An `UnsupportedMessage` to send back to the sender if the version is not supported, the
+    /// envelope contains another kind of message, or the payload is not a valid `M`
+    pub fn open(raw: &[u8], supported: ProtocolVersionRange) -> Result<Self, UnsupportedMessage> {
+        let codec = Codec::detect(raw);
+        let unsupported = |header: EnvelopeHeader, reason| UnsupportedMessage {
+            version: header.version,
+            kind: header.kind,
+            correlation_id: header.correlation_id,
+            reason,
+            supported,
+        };
+
+        match codec.decode::<EnvelopeHeader>(raw) {
+            Ok(header) if header.kind == M::KIND => {
+                if !supported.contains(header.version) {
+                    return Err(unsupported(header, UnsupportedReason::Version));
+                }
+                codec.decode::<Envelope<M>>(raw).map_err(|e| {
+                    unsupported(header, UnsupportedReason::InvalidMessage(e.to_string()))
+                })
+            }
+            header => {
+                // Not an envelope of M: it can be a message sent without envelope
+                if supported.contains(LEGACY_PROTOCOL_VERSION) {
+                    if let Ok(payload) = codec.decode::<M>(raw) {
+                        return Ok(Envelope {
+                            version: LEGACY_PROTOCOL_VERSION,
+                            kind: M::KIND.to_string(),
+                            correlation_id: None,
+                            payload,
+                        });
+                    }
+                }
+                Err(match header {
+                    Ok(header) => unsupported(header, UnsupportedReason::Kind),
+                    Err(e) => unsupported(
+                        EnvelopeHeader {
+                            version: LEGACY_PROTOCOL_VERSION,
+                            kind: String::new(),
+                            correlation_id: None,
+                        },
+                        UnsupportedReason::InvalidMessage(e.to_string()),
+                    ),
+                })
+            }
+        }
+    }
+}
+
+/// Why a message was not accepted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnsupportedReason {
+    /// The protocol version is outside of the supported range
+    Version,
+    /// The receiver does not handle this kind of message
+    Kind,
+    /// The payload can not be deserialized, e.g. a variant added in a newer version
+    InvalidMessage(String),
+}
+
+/**
+ * Response to a message that the receiver can not handle, sent instead of silently dropping it
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnsupportedMessage {
+    pub version: u16,
+    pub kind: String,
+    pub correlation_id: Option<u64>,
+    pub reason: UnsupportedReason,
+    pub supported: ProtocolVersionRange,
+}
+
+impl DroneSend for UnsupportedMessage {}
+impl MessageKind for UnsupportedMessage {
+    const KIND: &'static str = "UnsupportedMessage";
+}
+impl Response for UnsupportedMessage {}
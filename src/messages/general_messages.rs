@@ -2,6 +2,8 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use wg_2024::network::NodeId;
 
+use super::envelope::ProtocolVersionRange;
+
 /**
  * Represents a message that can be sent between nodes.
  * Contains the source node id, the session id and the content of the message.
@@ -13,16 +15,135 @@ pub struct Message<M: DroneSend> {
     pub content: M,
 }
 
+/// Errors while serializing or deserializing a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DroneSendError {
+    /// The message can not be serialized, e.g. a map with keys that are not strings or numbers
+    Serialize(String),
+    /// The payload is not a valid message of the expected type
+    Deserialize(String),
+    /// The payload was encoded with a version of the binary codec that is not supported
+    UnsupportedVersion(u8),
+}
+
+impl std::fmt::Display for DroneSendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DroneSendError::Serialize(error) => write!(f, "serialization failed: {error}"),
+            DroneSendError::Deserialize(error) => write!(f, "deserialization failed: {error}"),
+            DroneSendError::UnsupportedVersion(version) => {
+                write!(f, "unsupported binary codec version {version}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DroneSendError {}
+
+/// First byte of a message encoded with `Codec::Binary`. It can never start a UTF-8 string, so
+/// binary messages can not be confused with JSON ones
+pub const BINARY_MAGIC: u8 = 0xC0;
+/// Version of the binary encoding, sent after `BINARY_MAGIC`
+pub const BINARY_VERSION: u8 = 1;
+
+/**
+ * Wire format of a message. JSON is the default, to stay compatible with the nodes that only
+ * know `stringify`/`from_string`; the binary encoding is much more compact for messages carrying
+ * bytes, such as media files.
+ * Binary messages are prefixed by `BINARY_MAGIC` and `BINARY_VERSION`, so the receiver can
+ * detect the codec from the payload itself
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    Json,
+    Binary,
+}
+
+impl Codec {
+    /// The codec a payload was encoded with
+    #[must_use]
+    pub fn detect(raw: &[u8]) -> Codec {
+        if raw.first() == Some(&BINARY_MAGIC) {
+            Codec::Binary
+        } else {
+            Codec::Json
+        }
+    }
+
+    pub fn encode<M: Serialize + ?Sized>(self, message: &M) -> Result<Vec<u8>, DroneSendError> {
+        match self {
+            Codec::Json => {
+                serde_json::to_vec(message).map_err(|e| DroneSendError::Serialize(e.to_string()))
+            }
+            Codec::Binary => {
+                let mut raw = vec![BINARY_MAGIC, BINARY_VERSION];
+                bincode::serialize_into(&mut raw, message)
+                    .map_err(|e| DroneSendError::Serialize(e.to_string()))?;
+                Ok(raw)
+            }
+        }
+    }
+
+    /// Decode a payload with this codec, without looking at the prefix (see `detect`)
+    pub fn decode<M: DeserializeOwned>(self, raw: &[u8]) -> Result<M, DroneSendError> {
+        match self {
+            Codec::Json => {
+                serde_json::from_slice(raw).map_err(|e| DroneSendError::Deserialize(e.to_string()))
+            }
+            Codec::Binary => match raw {
+                [BINARY_MAGIC, BINARY_VERSION, data @ ..] => bincode::deserialize(data)
+                    .map_err(|e| DroneSendError::Deserialize(e.to_string())),
+                [BINARY_MAGIC, version, ..] => Err(DroneSendError::UnsupportedVersion(*version)),
+                _ => Err(DroneSendError::Deserialize(
+                    "missing binary codec prefix".to_string(),
+                )),
+            },
+        }
+    }
+}
+
 /**
  * Serialization/Deserialization of the message
  */
 pub trait DroneSend: Serialize + DeserializeOwned {
+    /// Serialize the message as JSON
+    ///
+    /// # Panics
+    /// If the message can not be serialized, use `try_stringify` to handle the error
     fn stringify(&self) -> String {
-        serde_json::to_string(self).unwrap()
+        self.try_stringify().unwrap()
+    }
+    /// Serialize the message as JSON
+    fn try_stringify(&self) -> Result<String, DroneSendError> {
+        serde_json::to_string(self).map_err(|e| DroneSendError::Serialize(e.to_string()))
+    }
+    /// Serialize the message as JSON bytes, ready to be disassembled
+    fn to_bytes(&self) -> Result<Vec<u8>, DroneSendError> {
+        Codec::Json.encode(self)
     }
-    fn from_string(raw: String) -> Result<Self, String> {
-        serde_json::from_str(raw.as_str()).map_err(|e| e.to_string())
+    fn from_string(raw: String) -> Result<Self, DroneSendError> {
+        Codec::Json.decode(raw.as_bytes())
     }
+    /// Deserialize a message from JSON bytes, e.g. the output of the `Assembler`
+    fn from_bytes(raw: &[u8]) -> Result<Self, DroneSendError> {
+        Codec::Json.decode(raw)
+    }
+    /// Serialize the message with the given codec
+    fn encode(&self, codec: Codec) -> Result<Vec<u8>, DroneSendError> {
+        codec.encode(self)
+    }
+    /// Deserialize a message, detecting the codec it was encoded with
+    fn decode(raw: &[u8]) -> Result<Self, DroneSendError> {
+        Codec::detect(raw).decode(raw)
+    }
+}
+
+/// Name of the kind of message, sent in the `Envelope`. It is part of the protocol: it must be a
+/// constant, the same on every node, not derived from the Rust type. Only needed by the messages
+/// sent in an `Envelope`
+pub trait MessageKind {
+    const KIND: &'static str;
 }
 
 pub trait Request: DroneSend {}
@@ -34,9 +155,13 @@ pub trait Response: DroneSend {}
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerTypeRequest {
     ServerType,
+    ProtocolVersion, // Ask the range of protocol versions supported by the server
 }
 
 impl DroneSend for ServerTypeRequest {}
+impl MessageKind for ServerTypeRequest {
+    const KIND: &'static str = "ServerTypeRequest";
+}
 impl Request for ServerTypeRequest {}
 
 /**
@@ -45,9 +170,13 @@ impl Request for ServerTypeRequest {}
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ServerTypeResponse {
     ServerType(ServerType),
+    ProtocolVersion(ProtocolVersionRange), // Range of protocol versions supported by the server
 }
 
 impl DroneSend for ServerTypeResponse {}
+impl MessageKind for ServerTypeResponse {
+    const KIND: &'static str = "ServerTypeResponse";
+}
 impl Response for ServerTypeResponse {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,3 +185,47 @@ pub enum ServerType {
     Text,
     Media,
 }
+
+/**
+ * Category of an error returned by a server
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    NotFound,        // The requested resource (e.g. a file) does not exist
+    NotRegistered,   // The sender or the destination is not registered to the server
+    WrongServerType, // The request is not handled by this type of server
+    InvalidRequest,  // The request is malformed
+    Internal,        // The server failed to handle a valid request
+}
+
+/**
+ * Error returned by a server instead of the expected response
+ *
+ * * `code: ErrorCode` - the category of the error
+ * * `message: String` - human readable description, to show in the UI
+ * * `request_id: Option<u64>` - the correlation id (see `Envelope`) or, for requests sent without
+ *   envelope, the session id of the request that caused the error, if known
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    pub message: String,
+    pub request_id: Option<u64>,
+}
+
+impl ErrorResponse {
+    #[must_use]
+    pub fn new(code: ErrorCode, message: impl Into<String>, request_id: Option<u64>) -> Self {
+        ErrorResponse {
+            code,
+            message: message.into(),
+            request_id,
+        }
+    }
+}
+
+impl DroneSend for ErrorResponse {}
+impl MessageKind for ErrorResponse {
+    const KIND: &'static str = "ErrorResponse";
+}
+impl Response for ErrorResponse {}
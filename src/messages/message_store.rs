@@ -0,0 +1,167 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use wg_2024::network::NodeId;
+
+use super::chat_messages::StoredMessage;
+
+/// Limits of a `MessageStore`, the oldest messages are dropped first when they are exceeded
+///
+/// * `max_history_per_conversation: usize` - messages kept in the history of each pair of clients
+/// * `max_history_age: Duration` - messages older than this, compared to the newest message of
+///   the conversation or to the time given to `sweep_expired`, are dropped from the history
+/// * `max_queued_per_client: usize` - messages kept for a client that can not be reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageStoreConfig {
+    pub max_history_per_conversation: usize,
+    pub max_history_age: Duration,
+    pub max_queued_per_client: usize,
+}
+
+impl Default for MessageStoreConfig {
+    fn default() -> Self {
+        MessageStoreConfig {
+            max_history_per_conversation: 1000,
+            max_history_age: Duration::from_secs(24 * 60 * 60),
+            max_queued_per_client: 256,
+        }
+    }
+}
+
+/// Store-and-forward of the messages handled by a chat server.
+///
+/// Every message sent through the server is added to the history with `store`. When the
+/// destination is registered but can not be reached, the message is also queued with `enqueue`:
+/// when the client registers again the server sends it `ChatResponse::QueuedMessages` with the
+/// messages returned by `take_queued`, oldest first
+#[derive(Debug, Default)]
+pub struct MessageStore {
+    config: MessageStoreConfig,
+    // history of every pair of clients, the smallest id first, oldest message first
+    history: HashMap<(NodeId, NodeId), VecDeque<StoredMessage>>,
+    queued: HashMap<NodeId, VecDeque<StoredMessage>>,
+    // number of queued messages dropped because the queue of the client was full
+    dropped_queued: u64,
+}
+
+// key of the conversation between two clients, the same in both directions
+fn conversation(a: NodeId, b: NodeId) -> (NodeId, NodeId) {
+    (a.min(b), a.max(b))
+}
+
+// milliseconds of a duration, as the timestamps of the messages
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+impl MessageStore {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_config(config: MessageStoreConfig) -> Self {
+        MessageStore {
+            config,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &MessageStoreConfig {
+        &self.config
+    }
+
+    /// Add a delivered message to the history
+    pub fn store(&mut self, message: StoredMessage) {
+        let max_len = self.config.max_history_per_conversation;
+        let oldest = message
+            .timestamp
+            .saturating_sub(millis(self.config.max_history_age));
+        let messages = self
+            .history
+            .entry(conversation(message.from, message.to))
+            .or_default();
+        messages.push_back(message);
+        while messages.len() > max_len
+            || messages
+                .front()
+                .is_some_and(|front| front.timestamp < oldest)
+        {
+            messages.pop_front();
+        }
+    }
+
+    /// Add a message that could not be delivered to the history and to the queue of its
+    /// destination. If the queue is full its oldest message is dropped
+    pub fn enqueue(&mut self, message: StoredMessage) {
+        let max_len = self.config.max_queued_per_client;
+        let queue = self.queued.entry(message.to).or_default();
+        queue.push_back(message.clone());
+        while queue.len() > max_len {
+            queue.pop_front();
+            self.dropped_queued += 1;
+        }
+        self.store(message);
+    }
+
+    /// Remove and return the messages queued for a client, oldest first
+    pub fn take_queued(&mut self, client_id: NodeId) -> Vec<StoredMessage> {
+        self.queued
+            .remove(&client_id)
+            .map(Vec::from)
+            .unwrap_or_default()
+    }
+
+    /// Number of messages waiting for a client
+    #[must_use]
+    pub fn queued_len(&self, client_id: NodeId) -> usize {
+        self.queued.get(&client_id).map_or(0, VecDeque::len)
+    }
+
+    /// Number of queued messages dropped because the queue of their destination was full
+    #[must_use]
+    pub fn dropped_queued(&self) -> u64 {
+        self.dropped_queued
+    }
+
+    /// Drop from the history the messages older than `max_history_age` at time `now`
+    /// (milliseconds since the Unix epoch), e.g. for conversations that are no longer active
+    pub fn sweep_expired(&mut self, now: u64) {
+        let oldest = now.saturating_sub(millis(self.config.max_history_age));
+        self.history.retain(|_, messages| {
+            while messages
+                .front()
+                .is_some_and(|front| front.timestamp < oldest)
+            {
+                messages.pop_front();
+            }
+            !messages.is_empty()
+        });
+    }
+
+    /// The last `limit` messages exchanged between two clients, oldest first.
+    /// With `since`, only the messages with a later timestamp are returned
+    #[must_use]
+    pub fn history(
+        &self,
+        client_id: NodeId,
+        with: NodeId,
+        since: Option<u64>,
+        limit: usize,
+    ) -> Vec<StoredMessage> {
+        let Some(messages) = self.history.get(&conversation(client_id, with)) else {
+            return Vec::new();
+        };
+        let mut messages: Vec<StoredMessage> = messages
+            .iter()
+            .rev()
+            .filter(|message| since.is_none_or(|since| message.timestamp > since))
+            .take(limit)
+            .cloned()
+            .collect();
+        messages.reverse();
+        messages
+    }
+}
@@ -1,4 +1,8 @@
 pub mod browser_messages;
 pub mod chat_messages;
 pub mod commander_messages;
+pub mod download_manager;
+pub mod envelope;
 pub mod general_messages;
+pub mod message_store;
+pub mod pending_requests;
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::TIMEOUT_TIMER_MS;
+
+/// A request waiting for its response
+struct PendingRequest<T> {
+    request: T,
+    sent_at: Instant,
+}
+
+/// Tracks the requests sent by a client that have not been answered yet.
+///
+/// Every request gets a correlation id, to send in its `Envelope`; the server echoes it in the
+/// response, which is matched with `resolve`. Requests without an answer for longer than the
+/// timeout are returned by `sweep_expired`, so that they can be sent again or reported as failed
+pub struct PendingRequests<T> {
+    next_id: u64,
+    pending: HashMap<u64, PendingRequest<T>>,
+    timeout: Duration,
+}
+
+impl<T> Default for PendingRequests<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PendingRequests<T> {
+    /// Create a tracker whose requests expire after `TIMEOUT_TIMER_MS`
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_timeout(Duration::from_millis(TIMEOUT_TIMER_MS))
+    }
+
+    #[must_use]
+    pub fn with_timeout(timeout: Duration) -> Self {
+        PendingRequests {
+            next_id: 0,
+            pending: HashMap::new(),
+            timeout,
+        }
+    }
+
+    #[must_use]
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Track a request sent now, returns its correlation id
+    pub fn register(&mut self, request: T) -> u64 {
+        self.register_at(request, Instant::now())
+    }
+
+    /// Same as `register`, but uses `now` as the time the request was sent
+    pub fn register_at(&mut self, request: T, now: Instant) -> u64 {
+        let correlation_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.pending.insert(
+            correlation_id,
+            PendingRequest {
+                request,
+                sent_at: now,
+            },
+        );
+        correlation_id
+    }
+
+    /// Stop tracking the request answered by a response, returning it.
+    /// `None` if the id is unknown, e.g. the request already expired or was answered twice
+    pub fn resolve(&mut self, correlation_id: u64) -> Option<T> {
+        self.pending
+            .remove(&correlation_id)
+            .map(|pending| pending.request)
+    }
+
+    #[must_use]
+    pub fn get(&self, correlation_id: u64) -> Option<&T> {
+        self.pending
+            .get(&correlation_id)
+            .map(|pending| &pending.request)
+    }
+
+    /// When a request was sent, `None` if it is not pending
+    #[must_use]
+    pub fn sent_at(&self, correlation_id: u64) -> Option<Instant> {
+        self.pending
+            .get(&correlation_id)
+            .map(|pending| pending.sent_at)
+    }
+
+    /// Remove the requests not answered within the timeout, returning them with their id
+    pub fn sweep_expired(&mut self, now: Instant) -> Vec<(u64, T)> {
+        let timeout = self.timeout;
+        let expired: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now.saturating_duration_since(pending.sent_at) >= timeout)
+            .map(|(&id, _)| id)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|id| self.resolve(id).map(|request| (id, request)))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
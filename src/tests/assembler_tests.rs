@@ -0,0 +1,240 @@
+#[cfg(test)]
+pub mod assembler_tests {
+    use std::time::{Duration, Instant};
+
+    use crate::assembler::{
+        assembler::{Assembler, AssemblerConfig, AssemblerError, SessionKey, SessionProgress},
+        disassembler::{Disassembler, FRAGMENT_DSIZE},
+    };
+
+    #[test]
+    fn should_reassemble_message() {
+        let message = "a".repeat(300).into_bytes();
+        let fragments = Disassembler::new().disassemble_message(message.clone(), 1);
+        let mut assembler = Assembler::new();
+
+        let mut result = None;
+        for fragment in fragments {
+            result = assembler.add_fragment(fragment, 1);
+        }
+
+        assert_eq!(result, Some(message));
+        assert_eq!(assembler.pending_sessions(), 0);
+    }
+
+    #[test]
+    fn should_evict_expired_sessions() {
+        let start = Instant::now();
+        let mut assembler = Assembler::with_timeout(Duration::from_millis(100));
+        let fragments = Disassembler::new().disassemble_message(vec![1; 300], 1);
+        let other = Disassembler::new().disassemble_message(vec![2; 300], 2);
+
+        // Session 1 stalls after the first fragment, session 2 keeps receiving
+        assembler.add_fragment_at(fragments[0].clone(), 1, start);
+        assembler.add_fragment_at(other[0].clone(), 2, start);
+        assembler.add_fragment_at(other[1].clone(), 2, start + Duration::from_millis(80));
+
+        assert_eq!(assembler.first_seen(2), Some(start));
+        assert_eq!(
+            assembler.last_seen(2),
+            Some(start + Duration::from_millis(80))
+        );
+
+        let expired = assembler.sweep_expired(start + Duration::from_millis(120));
+        assert_eq!(expired, vec![SessionKey::from(1)]);
+        assert_eq!(assembler.pending_sessions(), 1);
+        assert_eq!(assembler.first_seen(1), None);
+    }
+
+    #[test]
+    fn should_ignore_duplicate_fragments() {
+        let message: Vec<u8> = (0..=255).cycle().take(500).collect();
+        let fragments = Disassembler::new().disassemble_message(message.clone(), 1);
+        assert_eq!(fragments.len(), 4);
+        let mut assembler = Assembler::new();
+
+        // Fragment 0 is retransmitted while fragment 3 is still missing
+        assert_eq!(assembler.add_fragment(fragments[0].clone(), 1), None);
+        assert_eq!(assembler.add_fragment(fragments[1].clone(), 1), None);
+        assert_eq!(assembler.add_fragment(fragments[2].clone(), 1), None);
+        assert_eq!(assembler.add_fragment(fragments[0].clone(), 1), None);
+        assert_eq!(assembler.duplicate_fragments(), 1);
+
+        let result = assembler.add_fragment(fragments[3].clone(), 1);
+        assert_eq!(result, Some(message));
+    }
+
+    #[test]
+    fn should_report_missing_fragments() {
+        let fragments = Disassembler::new().disassemble_message(vec![7; 600], 3);
+        assert_eq!(fragments.len(), 5);
+        let mut assembler = Assembler::new();
+
+        assembler.add_fragment(fragments[0].clone(), 3);
+        assembler.add_fragment(fragments[2].clone(), 3);
+        assembler.add_fragment(fragments[4].clone(), 3);
+
+        assert_eq!(assembler.missing_fragments(3), Some(vec![1, 3]));
+        assert_eq!(assembler.missing_fragments(4), None);
+        assert_eq!(
+            assembler.incomplete_sessions().collect::<Vec<_>>(),
+            vec![SessionProgress {
+                source_id: None,
+                session_id: 3,
+                received: 3,
+                total_n_fragments: 5,
+            }]
+        );
+
+        assembler.add_fragment(fragments[1].clone(), 3);
+        assembler.add_fragment(fragments[3].clone(), 3);
+        assert_eq!(assembler.session_progress(3), None);
+        assert_eq!(assembler.incomplete_sessions().count(), 0);
+    }
+
+    #[test]
+    fn should_reject_inconsistent_fragments() {
+        let mut fragments = Disassembler::new().disassemble_message(vec![5; 300], 9);
+        let mut assembler = Assembler::new();
+        assert_eq!(
+            assembler.try_add_fragment(fragments[0].clone(), 9),
+            Ok(None)
+        );
+
+        let mut wrong_total = fragments[1].clone();
+        wrong_total.total_n_fragments = 7;
+        assert_eq!(
+            assembler.try_add_fragment(wrong_total, 9),
+            Err(AssemblerError::InconsistentTotal {
+                session_id: 9,
+                expected: 3,
+                found: 7,
+            })
+        );
+
+        let mut out_of_range = fragments[1].clone();
+        out_of_range.fragment_index = 3;
+        assert_eq!(
+            assembler.try_add_fragment(out_of_range, 9),
+            Err(AssemblerError::IndexOutOfRange {
+                session_id: 9,
+                fragment_index: 3,
+                total_n_fragments: 3,
+            })
+        );
+
+        let mut too_long = fragments[1].clone();
+        too_long.length = 200;
+        assert_eq!(
+            assembler.try_add_fragment(too_long, 9),
+            Err(AssemblerError::InvalidLength {
+                session_id: 9,
+                fragment_index: 1,
+                length: 200,
+            })
+        );
+
+        let mut tampered = fragments[0].clone();
+        tampered.data[0] = 6;
+        assert_eq!(
+            assembler.try_add_fragment(tampered, 9),
+            Err(AssemblerError::ConflictingDuplicate {
+                session_id: 9,
+                fragment_index: 0,
+            })
+        );
+
+        // The rejected fragments did not touch the session, which can still complete
+        fragments.remove(0);
+        let mut result = None;
+        for fragment in fragments {
+            result = assembler.add_fragment(fragment, 9);
+        }
+        assert_eq!(result, Some(vec![5; 300]));
+    }
+
+    #[test]
+    fn should_discard_session() {
+        let fragments = Disassembler::new().disassemble_message(vec![5; 300], 9);
+        let mut assembler = Assembler::new();
+        assembler.add_fragment(fragments[0].clone(), 9);
+
+        assert!(assembler.discard_session(9));
+        assert!(!assembler.discard_session(9));
+        assert_eq!(assembler.pending_sessions(), 0);
+    }
+
+    #[test]
+    fn should_reject_sessions_over_limits() {
+        let mut assembler = Assembler::with_config(AssemblerConfig {
+            max_fragments_per_session: 4,
+            ..AssemblerConfig::default()
+        });
+        let mut fragment = Disassembler::new().disassemble_message(vec![1; 10], 1)[0].clone();
+        fragment.total_n_fragments = u64::MAX;
+
+        assert_eq!(
+            assembler.try_add_fragment(fragment, 1),
+            Err(AssemblerError::TooManyFragments {
+                session_id: 1,
+                total_n_fragments: u64::MAX,
+                max: 4,
+            })
+        );
+        assert_eq!(assembler.rejected_fragments(), 1);
+        assert_eq!(assembler.pending_sessions(), 0);
+    }
+
+    #[test]
+    fn should_evict_least_recently_seen_sessions() {
+        let start = Instant::now();
+        let mut assembler = Assembler::with_config(AssemblerConfig {
+            max_sessions: 2,
+            max_buffered_bytes: 3 * FRAGMENT_DSIZE,
+            ..AssemblerConfig::default()
+        });
+        let fragments = Disassembler::new().disassemble_message(vec![1; 1000], 0);
+
+        assembler.add_fragment_at(fragments[0].clone(), 1, start);
+        assembler.add_fragment_at(fragments[0].clone(), 2, start + Duration::from_millis(1));
+        assembler.add_fragment_at(fragments[1].clone(), 1, start + Duration::from_millis(2));
+
+        // A third session evicts session 2, the least recently seen
+        assembler.add_fragment_at(fragments[0].clone(), 3, start + Duration::from_millis(3));
+        assert_eq!(assembler.evicted_sessions(), 1);
+        assert!(assembler.session_progress(2).is_none());
+        assert_eq!(assembler.buffered_bytes(), 3 * FRAGMENT_DSIZE);
+
+        // The buffer is full, session 1 is evicted to make room for session 3
+        assembler.add_fragment_at(fragments[1].clone(), 3, start + Duration::from_millis(4));
+        assert_eq!(assembler.evicted_sessions(), 2);
+        assert!(assembler.session_progress(1).is_none());
+        assert_eq!(assembler.buffered_bytes(), 2 * FRAGMENT_DSIZE);
+
+        // A single session can not exceed the buffer size
+        assembler.add_fragment_at(fragments[2].clone(), 3, start + Duration::from_millis(5));
+        assert_eq!(
+            assembler.try_add_fragment_at(fragments[3].clone(), 3, start),
+            Err(AssemblerError::LimitExceeded { session_id: 3 })
+        );
+        assert_eq!(assembler.rejected_fragments(), 1);
+    }
+
+    #[test]
+    fn should_keep_sessions_of_different_sources_apart() {
+        let first = Disassembler::new().disassemble_message(vec![1; 200], 5);
+        let second = Disassembler::new().disassemble_message(vec![2; 200], 5);
+        let mut assembler = Assembler::new();
+
+        // Two clients use the same session id, their fragments interleave
+        assert_eq!(assembler.add_fragment(first[0].clone(), (11, 5)), None);
+        assert_eq!(assembler.add_fragment(second[0].clone(), (12, 5)), None);
+        assert_eq!(assembler.missing_fragments((12, 5)), Some(vec![1]));
+        assert_eq!(assembler.missing_fragments(5), None);
+
+        let result = assembler.add_fragment(second[1].clone(), SessionKey::new(12, 5));
+        assert_eq!(result, Some(vec![2; 200]));
+        let result = assembler.add_fragment(first[1].clone(), SessionKey::new(11, 5));
+        assert_eq!(result, Some(vec![1; 200]));
+    }
+}
@@ -0,0 +1,70 @@
+#[cfg(test)]
+pub mod browser_messages_tests {
+    use crate::messages::browser_messages::{
+        content_hash, BrowserRequest, BrowserResponse, BrowserResponseWrapper, FileId, FileKind,
+        FileMetadata,
+    };
+    use crate::messages::commander_messages::SimControllerMessage;
+    use crate::messages::general_messages::{Codec, DroneSend};
+    use std::collections::HashMap;
+
+    #[test]
+    fn should_compute_fnv1a_hash() {
+        assert_eq!(content_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(content_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(content_hash(b"ab"), content_hash(b"ba"));
+    }
+
+    #[test]
+    fn should_serialize_detailed_file_list() {
+        let file = FileMetadata::for_content(
+            FileId(3),
+            "Rust".to_string(),
+            FileKind::Text,
+            "text/plain".to_string(),
+            b"fearless concurrency",
+            1_700_000_000_000,
+        );
+        assert_eq!(file.size, 20);
+        assert_eq!(file.content_hash, content_hash(b"fearless concurrency"));
+
+        let response =
+            BrowserResponseWrapper::Chat(BrowserResponse::FileListDetailed(vec![file.clone()]));
+        for codec in [Codec::Json, Codec::Binary] {
+            let raw = response.encode(codec).unwrap();
+            assert!(matches!(
+                BrowserResponseWrapper::decode(&raw),
+                Ok(BrowserResponseWrapper::Chat(BrowserResponse::FileListDetailed(files)))
+                    if files == vec![file.clone()]
+            ));
+        }
+    }
+
+    #[test]
+    fn should_keep_legacy_file_ids() {
+        // Servers using u8 ids send and expect bare numbers
+        let raw = r#"{"FileList":[1,2,255]}"#;
+        assert!(matches!(
+            BrowserResponse::from_string(raw.to_string()),
+            Ok(BrowserResponse::FileList(ids)) if ids == vec![FileId(1), FileId(2), FileId(255)]
+        ));
+        assert_eq!(
+            BrowserRequest::TextFileRequest(FileId::from(7u8)).stringify(),
+            r#"{"TextFileRequest":7}"#
+        );
+
+        assert_eq!(FileId(255).as_legacy(), Some(255));
+        assert_eq!(FileId(256).as_legacy(), None);
+
+        let message = SimControllerMessage::TextWithReferences(
+            FileId(1000),
+            "see image".to_string(),
+            HashMap::from([(FileId(1001), vec![1, 2])]),
+        );
+        assert!(matches!(
+            SimControllerMessage::from_string(message.stringify()),
+            Ok(SimControllerMessage::TextWithReferences(FileId(1000), _, references))
+                if references.contains_key(&FileId(1001))
+        ));
+    }
+}
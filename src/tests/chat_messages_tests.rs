@@ -0,0 +1,200 @@
+#[cfg(test)]
+pub mod chat_messages_tests {
+    use crate::messages::browser_messages::FileId;
+    use crate::messages::chat_messages::{
+        ChannelInfo, ChatContent, ChatContentError, ChatRequest, ChatRequestWrapper, ChatResponse,
+        ChatResponseWrapper, MAX_ATTACHMENT_SIZE,
+    };
+    use crate::messages::general_messages::{Codec, DroneSend};
+
+    #[test]
+    fn should_serialize_channel_messages() {
+        let request = ChatRequestWrapper::Chat(ChatRequest::SendChannelMessage {
+            from: 4,
+            channel_id: 2,
+            message: "hi all".into(),
+        });
+        assert!(matches!(
+            ChatRequestWrapper::from_bytes(&request.to_bytes().unwrap()),
+            Ok(ChatRequestWrapper::Chat(ChatRequest::SendChannelMessage {
+                from: 4,
+                channel_id: 2,
+                ..
+            }))
+        ));
+
+        let channels = vec![ChannelInfo {
+            id: 2,
+            name: "rustafarians".to_string(),
+            members: vec![4, 5],
+        }];
+        let response = ChatResponseWrapper::Chat(ChatResponse::ChannelList(channels.clone()));
+        let raw = response.encode(Codec::Binary).unwrap();
+        assert!(matches!(
+            ChatResponseWrapper::decode(&raw),
+            Ok(ChatResponseWrapper::Chat(ChatResponse::ChannelList(received))) if received == channels
+        ));
+    }
+
+    #[test]
+    fn should_serialize_presence_messages() {
+        let raw = ChatRequestWrapper::Chat(ChatRequest::Unregister(4))
+            .to_bytes()
+            .unwrap();
+        assert!(matches!(
+            ChatRequestWrapper::from_bytes(&raw),
+            Ok(ChatRequestWrapper::Chat(ChatRequest::Unregister(4)))
+        ));
+
+        for response in [ChatResponse::ClientJoined(5), ChatResponse::ClientLeft(5)] {
+            let raw = ChatResponseWrapper::Chat(response).to_bytes().unwrap();
+            assert!(matches!(
+                ChatResponseWrapper::from_bytes(&raw),
+                Ok(ChatResponseWrapper::Chat(
+                    ChatResponse::ClientJoined(5) | ChatResponse::ClientLeft(5)
+                ))
+            ));
+        }
+    }
+
+    #[test]
+    fn should_serialize_receipts() {
+        let raw = ChatRequestWrapper::Chat(ChatRequest::Delivered {
+            from: 5,
+            to: 4,
+            message_id: 9,
+        })
+        .encode(Codec::Binary)
+        .unwrap();
+        assert!(matches!(
+            ChatRequestWrapper::decode(&raw),
+            Ok(ChatRequestWrapper::Chat(ChatRequest::Delivered {
+                from: 5,
+                to: 4,
+                message_id: 9,
+            }))
+        ));
+
+        let raw = ChatResponseWrapper::Chat(ChatResponse::Read(5, 9))
+            .to_bytes()
+            .unwrap();
+        assert!(matches!(
+            ChatResponseWrapper::from_bytes(&raw),
+            Ok(ChatResponseWrapper::Chat(ChatResponse::Read(5, 9)))
+        ));
+    }
+
+    #[test]
+    fn should_accept_messages_without_id() {
+        let raw = r#"{"Chat":{"SendMessage":{"from":4,"to":5,"message":"hi"}}}"#;
+        assert!(matches!(
+            ChatRequestWrapper::from_string(raw.to_string()),
+            Ok(ChatRequestWrapper::Chat(ChatRequest::SendMessage {
+                message_id: None,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn should_serialize_rich_content() {
+        let contents = [
+            ChatContent::Reply {
+                reply_to: 3,
+                text: "sure".to_string(),
+            },
+            ChatContent::Delete(3),
+            ChatContent::Attachment {
+                server_id: 11,
+                media_id: FileId(2),
+                data: vec![0xff, 0xd8],
+            },
+        ];
+        for content in contents {
+            let response = ChatResponseWrapper::Chat(ChatResponse::MessageFrom {
+                from: 4,
+                message: content.clone(),
+                message_id: Some(7),
+            });
+            for codec in [Codec::Json, Codec::Binary] {
+                let raw = response.encode(codec).unwrap();
+                assert!(matches!(
+                    ChatResponseWrapper::decode(&raw),
+                    Ok(ChatResponseWrapper::Chat(ChatResponse::MessageFrom { message, .. })) if message == content
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn should_read_legacy_text_messages() {
+        let raw = r#"{"Chat":{"MessageFrom":{"from":4,"message":[104,105]}}}"#;
+        assert!(matches!(
+            ChatResponseWrapper::from_string(raw.to_string()),
+            Ok(ChatResponseWrapper::Chat(ChatResponse::MessageFrom { message, .. }))
+                if message == ChatContent::from("hi")
+        ));
+
+        let raw = r#"{"Chat":{"SendMessage":{"from":4,"to":5,"message":"Text"}}}"#;
+        assert!(matches!(
+            ChatRequestWrapper::from_string(raw.to_string()),
+            Ok(ChatRequestWrapper::Chat(ChatRequest::SendMessage { message, .. }))
+                if message.text() == Some("Text")
+        ));
+    }
+
+    #[test]
+    fn should_read_legacy_channel_messages() {
+        let raw = r#"{"Chat":{"ChannelMessageFrom":[2,4,[104,105]]}}"#;
+        assert!(matches!(
+            ChatResponseWrapper::from_string(raw.to_string()),
+            Ok(ChatResponseWrapper::Chat(ChatResponse::ChannelMessageFrom(2, 4, message)))
+                if message == ChatContent::from("hi")
+        ));
+
+        let raw = r#"{"Chat":{"SendChannelMessage":{"from":4,"channel_id":2,"message":"hi"}}}"#;
+        assert!(matches!(
+            ChatRequestWrapper::from_string(raw.to_string()),
+            Ok(ChatRequestWrapper::Chat(ChatRequest::SendChannelMessage { message, .. }))
+                if message.text() == Some("hi")
+        ));
+
+        let response = ChatResponseWrapper::Chat(ChatResponse::ChannelMessageFrom(
+            2,
+            4,
+            ChatContent::Delete(9),
+        ));
+        let raw = response.encode(Codec::Binary).unwrap();
+        assert!(matches!(
+            ChatResponseWrapper::decode(&raw),
+            Ok(ChatResponseWrapper::Chat(ChatResponse::ChannelMessageFrom(
+                2,
+                4,
+                ChatContent::Delete(9)
+            )))
+        ));
+    }
+
+    #[test]
+    fn should_limit_attachment_size() {
+        let attachment = ChatContent::attachment(11, FileId(2), vec![0; MAX_ATTACHMENT_SIZE]);
+        assert!(attachment.is_ok_and(|content| content.validate().is_ok()));
+
+        assert_eq!(
+            ChatContent::attachment(11, FileId(2), vec![0; MAX_ATTACHMENT_SIZE + 1]),
+            Err(ChatContentError::AttachmentTooLarge {
+                size: MAX_ATTACHMENT_SIZE + 1,
+                max: MAX_ATTACHMENT_SIZE
+            })
+        );
+
+        // Content built directly, e.g. deserialized, is checked by validate
+        let content = ChatContent::Attachment {
+            server_id: 11,
+            media_id: FileId(2),
+            data: vec![0; MAX_ATTACHMENT_SIZE + 1],
+        };
+        assert!(content.validate().is_err());
+        assert!(ChatContent::from("hi").validate().is_ok());
+    }
+}
@@ -0,0 +1,183 @@
+#[cfg(test)]
+pub mod compression_tests {
+    use rand::Rng;
+    use wg_2024::network::SourceRoutingHeader;
+
+    use crate::assembler::{
+        assembler::{Assembler, AssemblerError},
+        compression::{
+            compress, decompress, is_compressed, Compression, COMPRESSED_HEADER,
+            UNCOMPRESSED_HEADER,
+        },
+        disassembler::{Disassembler, DisassemblerError},
+        message_assembler::MessageAssembler,
+    };
+    use crate::messages::browser_messages::{BrowserResponse, FileId};
+    use crate::messages::general_messages::{Codec, DroneSend};
+
+    #[test]
+    fn should_round_trip_compressed_data() {
+        let mut rng = rand::thread_rng();
+        let random: Vec<u8> = (0..5000).map(|_| rng.gen()).collect();
+        let inputs = vec![
+            Vec::new(),
+            vec![7],
+            vec![0; 10_000],
+            "abcabcabcabd".repeat(100).into_bytes(),
+            random,
+        ];
+
+        for input in inputs {
+            let compressed = compress(&input);
+            assert!(is_compressed(&compressed));
+            assert_eq!(decompress(&compressed, input.len()), Some(input.clone()));
+        }
+    }
+
+    #[test]
+    fn should_reject_corrupted_data() {
+        let compressed = compress(&"hello hello hello hello".repeat(10).into_bytes());
+
+        // Bigger than the allowed size
+        assert_eq!(decompress(&compressed, 10), None);
+        // Truncated
+        assert_eq!(decompress(&compressed[..compressed.len() - 1], 1000), None);
+        // Not compressed at all
+        assert_eq!(decompress(b"{\"FileList\":[]}", 1000), None);
+        // Match pointing before the start of the output
+        assert_eq!(decompress(&[0xC1, 8, 0x80, 0x01, 0x00], 1000), None);
+        // Announcing a huge size does not make it allocated up front
+        let mut huge = vec![COMPRESSED_HEADER];
+        huge.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x00, 0x07]);
+        assert_eq!(decompress(&huge, usize::MAX), None);
+    }
+
+    #[test]
+    fn should_compress_in_the_fragmentation_pipeline() {
+        let response =
+            BrowserResponse::TextFile(FileId(1), "lorem ipsum dolor sit amet ".repeat(200));
+        let payload = response.stringify().into_bytes();
+
+        let plain = Disassembler::new().disassemble_message(payload.clone(), 1);
+        let mut disassembler = Disassembler::new();
+        disassembler.set_compression(Compression::Lz);
+        let fragments = disassembler.disassemble_message(payload.clone(), 1);
+        assert!(fragments.len() < plain.len());
+
+        let mut assembler = Assembler::new();
+        assembler.set_compression(Compression::Lz);
+        let mut result = None;
+        for fragment in fragments {
+            result = assembler.add_fragment(fragment, 1);
+        }
+        assert_eq!(result, Some(payload));
+    }
+
+    #[test]
+    fn should_report_invalid_compressed_message() {
+        let mut corrupted = compress(&[1; 1000]);
+        corrupted.truncate(corrupted.len() - 1);
+        let fragments = Disassembler::new().disassemble_message(corrupted, 4);
+
+        let mut assembler = Assembler::new();
+        assembler.set_compression(Compression::Lz);
+        assert_eq!(
+            assembler.try_add_fragment(fragments[0].clone(), 4),
+            Err(AssemblerError::InvalidCompression { session_id: 4 })
+        );
+
+        // Messages without a header come from nodes not using compression
+        let fragments = Disassembler::new().disassemble_message(b"{}".to_vec(), 5);
+        assert_eq!(
+            assembler.try_add_fragment(fragments[0].clone(), 5),
+            Ok(Some(b"{}".to_vec()))
+        );
+    }
+
+    #[test]
+    fn should_tag_messages_that_are_not_compressed() {
+        let mut disassembler = Disassembler::new();
+        disassembler.set_compression(Compression::Lz);
+        let mut assembler = Assembler::new();
+        assembler.set_compression(Compression::Lz);
+
+        // Not worth compressing, sent with the uncompressed header, even if it looks compressed
+        let payload = vec![COMPRESSED_HEADER, 1, 2];
+        let fragments = disassembler.disassemble_message(payload.clone(), 6);
+        assert_eq!(fragments[0].data[0], UNCOMPRESSED_HEADER);
+        assert_eq!(
+            assembler.try_add_fragment(fragments[0].clone(), 6),
+            Ok(Some(payload))
+        );
+    }
+
+    #[test]
+    fn should_not_decompress_by_default() {
+        // Raw payloads starting with the header byte are left untouched
+        let payload = vec![COMPRESSED_HEADER, 0xFF, 0xFF];
+        let fragments = Disassembler::new().disassemble_message(payload.clone(), 7);
+
+        let mut assembler = Assembler::new();
+        assert_eq!(
+            assembler.try_add_fragment(fragments[0].clone(), 7),
+            Ok(Some(payload))
+        );
+    }
+
+    #[test]
+    fn should_detect_compression_of_each_sender() {
+        let response =
+            BrowserResponse::TextFile(FileId(1), "lorem ipsum dolor sit amet ".repeat(200));
+        let mut receiver = MessageAssembler::<BrowserResponse>::new();
+
+        let mut compressing = Disassembler::new();
+        compressing.set_compression(Compression::Lz);
+        let mut binary = Disassembler::new();
+        binary.set_compression(Compression::Lz);
+        binary.set_codec(Codec::Binary);
+        let senders = [(10, compressing), (20, Disassembler::new()), (30, binary)];
+
+        for (source_id, mut disassembler) in senders {
+            let header = SourceRoutingHeader {
+                hop_index: 1,
+                hops: vec![source_id, 1, 40],
+            };
+            let packets = disassembler
+                .disassemble_packets(&response, header, 1)
+                .unwrap();
+            let mut result = None;
+            for packet in &packets {
+                result = receiver.handle_packet(packet).unwrap();
+            }
+            let message = result.unwrap();
+            assert_eq!(message.source_id, source_id);
+            assert!(matches!(
+                message.content,
+                BrowserResponse::TextFile(FileId(1), text) if text.len() == 27 * 200
+            ));
+        }
+    }
+
+    #[test]
+    fn should_check_the_size_of_the_compressed_message() {
+        let mut rng = rand::thread_rng();
+        let mut disassembler = Disassembler::new();
+        disassembler.set_compression(Compression::Lz);
+        disassembler.set_max_message_size(1000);
+
+        // Incompressible: sent with the uncompressed header, one byte over the limit
+        let random: Vec<u8> = (0..1000).map(|_| rng.gen()).collect();
+        assert!(matches!(
+            disassembler.try_disassemble_message(random, 1),
+            Err(DisassemblerError::MessageTooLarge {
+                size: 1001,
+                max: 1000
+            })
+        ));
+
+        // Bigger than the limit, but not once compressed
+        assert!(disassembler
+            .try_disassemble_message(vec![0; 5000], 2)
+            .is_ok());
+    }
+}
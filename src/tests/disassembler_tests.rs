@@ -0,0 +1,208 @@
+#[cfg(test)]
+pub mod disassembler_tests {
+    use wg_2024::network::SourceRoutingHeader;
+    use wg_2024::packet::PacketType;
+
+    use crate::assembler::{
+        assembler::{Assembler, AssemblerConfig},
+        disassembler::{Disassembler, DisassemblerError, DEFAULT_MAX_MESSAGE_SIZE, FRAGMENT_DSIZE},
+        message_assembler::MessageAssembler,
+        session_id_allocator::SessionIdAllocator,
+    };
+    use crate::messages::chat_messages::{ChatRequest, ChatRequestWrapper};
+    use crate::tests::topology_tests::topology_tests::update_topology;
+    use crate::topology::Topology;
+
+    #[test]
+    fn should_build_packets_with_routing_header() {
+        let request = ChatRequestWrapper::Chat(ChatRequest::SendMessage {
+            from: 10,
+            to: 20,
+            message: "x".repeat(300).into(),
+            message_id: Some(1),
+        });
+        let header = SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![10, 1, 30],
+        };
+
+        let packets = Disassembler::new()
+            .disassemble_packets(&request, header.clone(), 7)
+            .unwrap();
+        assert_eq!(packets.len(), 3);
+        for (i, packet) in packets.iter().enumerate() {
+            assert_eq!(packet.session_id, 7);
+            assert_eq!(packet.routing_header.hops, header.hops);
+            match &packet.pack_type {
+                PacketType::MsgFragment(fragment) => {
+                    assert_eq!(fragment.fragment_index, i as u64);
+                    assert_eq!(fragment.total_n_fragments, 3);
+                }
+                other => panic!("unexpected packet {other:?}"),
+            }
+        }
+
+        // The packets can be reassembled into the original request
+        let mut assembler = MessageAssembler::<ChatRequestWrapper>::new();
+        let mut result = None;
+        for packet in &packets {
+            result = assembler.handle_packet(packet).unwrap();
+        }
+        assert!(matches!(
+            result.unwrap().content,
+            ChatRequestWrapper::Chat(ChatRequest::SendMessage {
+                from: 10,
+                to: 20,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_route_packets_on_topology() {
+        let mut topology = Topology::new();
+        update_topology(
+            &mut topology,
+            vec![1, 2, 10, 30, 40],
+            vec![(10, 1), (1, 2), (2, 30)],
+        );
+        let request = ChatRequestWrapper::Chat(ChatRequest::ClientList);
+        let mut disassembler = Disassembler::new();
+
+        let packets = disassembler
+            .disassemble_packets_to(&request, &mut topology, 10, 30, 1)
+            .unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].routing_header.hops, vec![10, 1, 2, 30]);
+        assert_eq!(packets[0].routing_header.hop_index, 1);
+
+        // Node 40 is not connected to anything
+        assert_eq!(
+            disassembler
+                .disassemble_packets_to(&request, &mut topology, 10, 40, 2)
+                .unwrap_err(),
+            DisassemblerError::Unreachable { destination_id: 40 }
+        );
+    }
+
+    #[test]
+    fn should_allocate_unique_session_ids() {
+        let mut first = SessionIdAllocator::new(1);
+        let mut second = SessionIdAllocator::new(2);
+
+        let first_ids: Vec<u64> = (0..100).map(|_| first.next_id()).collect();
+        let second_ids: Vec<u64> = (0..100).map(|_| second.next_id()).collect();
+        assert!(first_ids.iter().all(|id| !second_ids.contains(id)));
+        assert!(first_ids.windows(2).all(|ids| ids[0] < ids[1]));
+        assert_eq!(SessionIdAllocator::owner(second_ids[50]), 2);
+
+        // A restored allocator continues from where the previous one stopped
+        let mut restored = SessionIdAllocator::restore(1, first.next_counter());
+        assert_eq!(restored.next_id(), first.next_id());
+    }
+
+    #[test]
+    fn should_disassemble_with_new_session() {
+        let request = ChatRequestWrapper::Chat(ChatRequest::ClientList);
+        let header = SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![10, 1, 30],
+        };
+
+        let mut disassembler = Disassembler::for_node(10);
+        let (first, packets) = disassembler
+            .disassemble_packets_new_session(&request, header.clone())
+            .unwrap();
+        let (second, _) = disassembler
+            .disassemble_packets_new_session(&request, header.clone())
+            .unwrap();
+        assert_ne!(first, second);
+        assert_eq!(packets[0].session_id, first);
+        assert_eq!(SessionIdAllocator::owner(first), 10);
+
+        assert_eq!(
+            Disassembler::new()
+                .disassemble_packets_new_session(&request, header)
+                .unwrap_err(),
+            DisassemblerError::NoSessionIdAllocator
+        );
+    }
+
+    #[test]
+    fn should_send_empty_message_as_single_fragment() {
+        let fragments = Disassembler::new().disassemble_message(Vec::new(), 1);
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(fragments[0].total_n_fragments, 1);
+        assert_eq!(fragments[0].length, 0);
+
+        let mut assembler = Assembler::new();
+        assert_eq!(
+            assembler.add_fragment(fragments[0].clone(), 1),
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn should_split_at_fragment_boundaries() {
+        for (size, expected) in [
+            (1, 1),
+            (FRAGMENT_DSIZE - 1, 1),
+            (FRAGMENT_DSIZE, 1),
+            (FRAGMENT_DSIZE + 1, 2),
+            (2 * FRAGMENT_DSIZE, 2),
+            (3 * FRAGMENT_DSIZE, 3),
+        ] {
+            let message: Vec<u8> = (0..size).map(|i| i as u8).collect();
+            let fragments = Disassembler::new().disassemble_message(message.clone(), 1);
+            assert_eq!(fragments.len(), expected, "message of {size} bytes");
+            assert!(fragments
+                .iter()
+                .all(|f| f.total_n_fragments == expected as u64));
+            let last = fragments.last().unwrap();
+            assert_eq!(last.length as usize, size - (expected - 1) * FRAGMENT_DSIZE);
+
+            let mut assembler = Assembler::new();
+            let mut result = None;
+            for fragment in fragments {
+                result = assembler.add_fragment(fragment, 1);
+            }
+            assert_eq!(result, Some(message));
+        }
+    }
+
+    #[test]
+    fn should_refuse_messages_over_max_size() {
+        let mut disassembler = Disassembler::new();
+        disassembler.set_max_message_size(2 * FRAGMENT_DSIZE);
+
+        assert_eq!(
+            disassembler
+                .try_disassemble_message(vec![0; 2 * FRAGMENT_DSIZE], 1)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            disassembler
+                .try_disassemble_message(vec![0; 2 * FRAGMENT_DSIZE + 1], 1)
+                .unwrap_err(),
+            DisassemblerError::MessageTooLarge {
+                size: 2 * FRAGMENT_DSIZE + 1,
+                max: 2 * FRAGMENT_DSIZE,
+            }
+        );
+    }
+
+    #[test]
+    fn should_agree_with_assembler_on_default_limits() {
+        let max_fragments = AssemblerConfig::default().max_fragments_per_session;
+        assert_eq!(
+            DEFAULT_MAX_MESSAGE_SIZE,
+            max_fragments as usize * FRAGMENT_DSIZE
+        );
+        assert_eq!(
+            Disassembler::new().max_message_size(),
+            DEFAULT_MAX_MESSAGE_SIZE
+        );
+    }
+}
@@ -0,0 +1,254 @@
+#[cfg(test)]
+pub mod download_manager_tests {
+    use std::time::{Duration, Instant};
+
+    use crate::messages::browser_messages::{content_hash, BrowserRequest, FileId, MediaFileChunk};
+    use crate::messages::download_manager::{DownloadConfig, DownloadError, DownloadManager};
+
+    const SERVER: u8 = 11;
+
+    fn manager() -> DownloadManager {
+        DownloadManager::with_config(DownloadConfig {
+            chunk_size: 100,
+            parallel_chunks: 3,
+            chunk_timeout: Duration::from_millis(500),
+            max_file_size: 1000,
+        })
+    }
+
+    fn content(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i % 251) as u8).collect()
+    }
+
+    // The chunks the server sends for the requests
+    fn serve(requests: Vec<(u8, BrowserRequest)>, content: &[u8]) -> Vec<MediaFileChunk> {
+        requests
+            .into_iter()
+            .map(|(server_id, request)| {
+                assert_eq!(server_id, SERVER);
+                match request {
+                    BrowserRequest::MediaFileRangeRequest { id, offset, len } => {
+                        MediaFileChunk::from_content(id, content, offset, len)
+                    }
+                    other => panic!("unexpected request {other:?}"),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_download_chunks_in_parallel() {
+        let file = content(450);
+        let mut manager = manager();
+        let now = Instant::now();
+        assert!(manager.start(SERVER, FileId(1)));
+        assert!(!manager.start(SERVER, FileId(1)));
+
+        // The size is unknown until the first chunk arrives
+        let first = serve(manager.next_requests(now), &file);
+        assert_eq!(first.len(), 1);
+        for chunk in first {
+            assert_eq!(manager.handle_chunk(SERVER, chunk), Ok(None));
+        }
+        assert_eq!(manager.progress(SERVER, FileId(1)), Some((100, Some(450))));
+
+        let chunks = serve(manager.next_requests(now), &file);
+        assert_eq!(chunks.len(), 3);
+        assert!(manager.next_requests(now).is_empty());
+        for chunk in chunks {
+            assert_eq!(manager.handle_chunk(SERVER, chunk), Ok(None));
+        }
+
+        let last = serve(manager.next_requests(now), &file);
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].data.len(), 50);
+        let result = manager.handle_chunk(SERVER, last[0].clone());
+        assert_eq!(result, Ok(Some(file)));
+        assert!(manager.is_empty());
+
+        // Late duplicates are not accepted anymore
+        assert_eq!(
+            manager.handle_chunk(SERVER, last[0].clone()),
+            Err(DownloadError::UnknownDownload {
+                server_id: SERVER,
+                id: FileId(1)
+            })
+        );
+    }
+
+    #[test]
+    fn should_request_again_lost_and_corrupted_chunks() {
+        let file = content(300);
+        let mut manager = manager();
+        let now = Instant::now();
+        manager.start(SERVER, FileId(2));
+        for chunk in serve(manager.next_requests(now), &file) {
+            manager.handle_chunk(SERVER, chunk).unwrap();
+        }
+
+        let mut chunks = serve(manager.next_requests(now), &file);
+        assert_eq!(chunks.len(), 2);
+        // The chunk at offset 100 is corrupted, the one at 200 is lost
+        let _lost = chunks.pop();
+        let mut corrupted = chunks.pop().unwrap();
+        corrupted.data[0] ^= 1;
+        assert_eq!(
+            manager.handle_chunk(SERVER, corrupted),
+            Err(DownloadError::ChunkChecksumMismatch {
+                id: FileId(2),
+                offset: 100
+            })
+        );
+
+        // The corrupted chunk is requested again at once, the lost one after the timeout
+        let retried = serve(manager.next_requests(now), &file);
+        assert_eq!(retried.len(), 1);
+        assert_eq!(retried[0].offset, 100);
+        manager.handle_chunk(SERVER, retried[0].clone()).unwrap();
+
+        let later = now + Duration::from_millis(500);
+        let retried = serve(manager.next_requests(later), &file);
+        assert_eq!(retried.len(), 1);
+        assert_eq!(retried[0].offset, 200);
+        assert_eq!(
+            manager.handle_chunk(SERVER, retried[0].clone()),
+            Ok(Some(file))
+        );
+    }
+
+    #[test]
+    fn should_restart_when_file_changes() {
+        let old = content(250);
+        let mut new = content(250);
+        new[200] = 0xff;
+        let mut manager = manager();
+        let now = Instant::now();
+        manager.start(SERVER, FileId(3));
+        for chunk in serve(manager.next_requests(now), &old) {
+            manager.handle_chunk(SERVER, chunk).unwrap();
+        }
+
+        let chunks = serve(manager.next_requests(now), &new);
+        assert_eq!(
+            manager.handle_chunk(SERVER, chunks[0].clone()),
+            Err(DownloadError::FileChanged { id: FileId(3) })
+        );
+        assert_eq!(manager.progress(SERVER, FileId(3)), Some((0, None)));
+
+        let mut result = None;
+        for _ in 0..3 {
+            for chunk in serve(manager.next_requests(now), &new) {
+                if let Some(file) = manager.handle_chunk(SERVER, chunk).unwrap() {
+                    result = Some(file);
+                }
+            }
+        }
+        assert_eq!(result, Some(new));
+    }
+
+    #[test]
+    fn should_serve_chunks_past_the_end() {
+        let file = content(10);
+        let chunk = MediaFileChunk::from_content(FileId(4), &file, 8, 100);
+        assert_eq!(chunk.data, file[8..].to_vec());
+        assert_eq!(chunk.total_size, 10);
+        assert!(MediaFileChunk::from_content(FileId(4), &file, 50, 100)
+            .data
+            .is_empty());
+
+        let mut manager = manager();
+        manager.start(SERVER, FileId(5));
+        let chunks = serve(manager.next_requests(Instant::now()), &[]);
+        assert_eq!(
+            manager.handle_chunk(SERVER, chunks[0].clone()),
+            Ok(Some(vec![]))
+        );
+    }
+
+    #[test]
+    fn should_reject_files_too_large() {
+        let file = content(1001);
+        let mut manager = manager();
+        manager.start(SERVER, FileId(6));
+        let chunks = serve(manager.next_requests(Instant::now()), &file);
+        assert_eq!(
+            manager.handle_chunk(SERVER, chunks[0].clone()),
+            Err(DownloadError::FileTooLarge {
+                id: FileId(6),
+                size: 1001,
+                max: 1000
+            })
+        );
+        assert!(manager.is_empty());
+    }
+
+    #[test]
+    fn should_request_lazily_the_chunks_of_huge_files() {
+        let mut manager = DownloadManager::with_config(DownloadConfig {
+            max_file_size: u64::MAX,
+            ..DownloadConfig::default()
+        });
+        let now = Instant::now();
+        manager.start(SERVER, FileId(7));
+        manager.next_requests(now);
+        let data = content(32 * 1024);
+        let chunk = MediaFileChunk {
+            id: FileId(7),
+            offset: 0,
+            total_size: u64::MAX,
+            chunk_hash: content_hash(&data),
+            data,
+            file_hash: 0,
+        };
+        assert_eq!(manager.handle_chunk(SERVER, chunk), Ok(None));
+        assert_eq!(manager.next_requests(now).len(), 4);
+    }
+
+    #[test]
+    fn should_request_the_rest_of_short_chunks() {
+        let file = content(250);
+        let mut manager = manager();
+        let now = Instant::now();
+        manager.start(SERVER, FileId(8));
+
+        // The server sends at most 60 bytes per chunk
+        let mut result = None;
+        for _ in 0..10 {
+            for (server_id, request) in manager.next_requests(now) {
+                let BrowserRequest::MediaFileRangeRequest { id, offset, len } = request else {
+                    panic!("unexpected request {request:?}");
+                };
+                assert!(len <= 100);
+                let chunk = MediaFileChunk::from_content(id, &file, offset, len.min(60));
+                if let Some(file) = manager.handle_chunk(server_id, chunk).unwrap() {
+                    result = Some(file);
+                }
+            }
+        }
+        assert_eq!(result, Some(file));
+        assert!(manager.is_empty());
+    }
+
+    #[test]
+    fn should_cancel_on_empty_chunks() {
+        let file = content(250);
+        let mut manager = manager();
+        let now = Instant::now();
+        manager.start(SERVER, FileId(9));
+        for chunk in serve(manager.next_requests(now), &file) {
+            manager.handle_chunk(SERVER, chunk).unwrap();
+        }
+
+        let mut chunks = serve(manager.next_requests(now), &file);
+        chunks[0].data.clear();
+        chunks[0].chunk_hash = content_hash(&[]);
+        assert_eq!(
+            manager.handle_chunk(SERVER, chunks[0].clone()),
+            Err(DownloadError::EmptyChunk {
+                id: FileId(9),
+                offset: 100
+            })
+        );
+        assert!(manager.is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+#[cfg(test)]
+pub mod e2e_tests {
+    use crate::e2e::cipher::{self, chacha20_block, chacha20poly1305_seal, hchacha20, poly1305};
+    use crate::e2e::session_keys::{fingerprint, E2eError, SessionKeys};
+    use crate::e2e::x25519::{public_key, x25519};
+
+    fn hex<const N: usize>(s: &str) -> [u8; N] {
+        let bytes: Vec<u8> = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect();
+        bytes.try_into().unwrap()
+    }
+
+    fn sequence<const N: usize>(start: u8) -> [u8; N] {
+        std::array::from_fn(|i| start + i as u8)
+    }
+
+    #[test]
+    fn should_match_x25519_vectors() {
+        // RFC 7748, section 5.2
+        let scalar = hex("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+        let u = hex("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+        let expected: [u8; 32] =
+            hex("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552");
+        assert_eq!(x25519(&scalar, &u), expected);
+
+        // RFC 7748, section 6.1
+        let alice = hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let bob = hex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let alice_public = public_key(&alice);
+        let bob_public = public_key(&bob);
+        assert_eq!(
+            alice_public,
+            hex::<32>("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a")
+        );
+        assert_eq!(
+            bob_public,
+            hex::<32>("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f")
+        );
+        let shared: [u8; 32] =
+            hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+        assert_eq!(x25519(&alice, &bob_public), shared);
+        assert_eq!(x25519(&bob, &alice_public), shared);
+    }
+
+    #[test]
+    fn should_match_chacha20_vectors() {
+        // RFC 8439, section 2.3.2
+        let block = chacha20_block(&sequence(0), 1, &hex("000000090000004a00000000"));
+        assert_eq!(block[..16], hex::<16>("10f1e7e4d13b5915500fdd1fa32071c4"));
+
+        // draft-irtf-cfrg-xchacha, section 2.2.1
+        let subkey = hchacha20(&sequence(0), &hex("000000090000004a0000000031415927"));
+        assert_eq!(
+            subkey,
+            hex::<32>("82413b4227b27bfed30e42508a877d73a0f9e4d58a74a853c12ec41326d3ecdc")
+        );
+    }
+
+    #[test]
+    fn should_match_poly1305_vectors() {
+        // RFC 8439, section 2.5.2
+        let key = hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b");
+        let tag = poly1305(&key, b"Cryptographic Forum Research Group");
+        assert_eq!(tag, hex::<16>("a8061dc1305136c6c22b8baf0c0127a9"));
+
+        // RFC 8439, section 2.8.2
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+        let sealed = chacha20poly1305_seal(
+            &sequence(0x80),
+            &hex("070000004041424344454647"),
+            &hex::<12>("50515253c0c1c2c3c4c5c6c7"),
+            plaintext,
+        );
+        assert_eq!(sealed.len(), plaintext.len() + cipher::TAG_SIZE);
+        assert_eq!(sealed[..16], hex::<16>("d31a8d34648e60db7b86afbc53ef7ec2"));
+        assert_eq!(
+            sealed[plaintext.len()..],
+            hex::<16>("1ae10b594f09e26a7e902ecbd0600691")
+        );
+    }
+
+    #[test]
+    fn should_reject_modified_ciphertext() {
+        let key = sequence(1);
+        let nonce = sequence(2);
+        let mut sealed = cipher::seal(&key, &nonce, b"ad", b"hello");
+        assert_eq!(
+            cipher::open(&key, &nonce, b"ad", &sealed),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(cipher::open(&key, &nonce, b"other", &sealed), None);
+        sealed[0] ^= 1;
+        assert_eq!(cipher::open(&key, &nonce, b"ad", &sealed), None);
+        assert_eq!(cipher::open(&key, &nonce, b"ad", &sealed[..10]), None);
+    }
+
+    #[test]
+    fn should_exchange_encrypted_messages() {
+        let mut alice = SessionKeys::new(4);
+        let mut bob = SessionKeys::new(5);
+        assert_eq!(alice.encrypt(5, b"hi"), Err(E2eError::NoSession(5)));
+
+        alice.establish(5, &bob.public_key()).unwrap();
+        bob.establish(4, &alice.public_key()).unwrap();
+        assert!(alice.has_session(5));
+
+        let payload = alice.encrypt(5, b"hi bob").unwrap();
+        assert_ne!(payload.ciphertext[..6], b"hi bob"[..]);
+        assert_eq!(bob.decrypt(4, &payload), Ok(b"hi bob".to_vec()));
+
+        // A message can not be reflected back to its sender
+        assert_eq!(
+            alice.decrypt(5, &payload),
+            Err(E2eError::DecryptionFailed(5))
+        );
+
+        // Nor read by another client
+        let mut mallory = SessionKeys::new(6);
+        mallory.establish(4, &alice.public_key()).unwrap();
+        assert_eq!(
+            mallory.decrypt(4, &payload),
+            Err(E2eError::DecryptionFailed(4))
+        );
+
+        bob.forget(4);
+        assert_eq!(bob.decrypt(4, &payload), Err(E2eError::NoSession(4)));
+        assert_eq!(
+            alice.establish(7, &[0u8; 32]),
+            Err(E2eError::InvalidPublicKey(7))
+        );
+    }
+
+    #[test]
+    fn should_keep_the_first_key_of_a_peer() {
+        let mut alice = SessionKeys::new(4);
+        let bob = SessionKeys::new(5);
+        let server = SessionKeys::new(1);
+        alice.establish(5, &bob.public_key()).unwrap();
+
+        // The same key can be received again, a different one is refused
+        assert_eq!(alice.establish(5, &bob.public_key()), Ok(()));
+        assert_eq!(
+            alice.establish(5, &server.public_key()),
+            Err(E2eError::KeyChanged(5))
+        );
+        assert_eq!(alice.peer_fingerprint(5), Some(bob.fingerprint()));
+
+        // After forgetting the session a new key is accepted
+        alice.forget(5);
+        assert_eq!(alice.peer_fingerprint(5), None);
+        assert_eq!(alice.establish(5, &server.public_key()), Ok(()));
+        assert_eq!(alice.peer_fingerprint(5), Some(server.fingerprint()));
+    }
+
+    #[test]
+    fn should_format_fingerprints() {
+        let key = sequence(0);
+        let fingerprint = fingerprint(&key);
+        assert!(fingerprint.starts_with("0001 0203 "));
+        assert_eq!(fingerprint.len(), 16 * 4 + 15);
+    }
+}
@@ -0,0 +1,140 @@
+#[cfg(test)]
+pub mod envelope_tests {
+    use std::time::{Duration, Instant};
+
+    use crate::messages::browser_messages::BrowserRequestWrapper;
+    use crate::messages::chat_messages::{
+        ChatRequest, ChatRequestWrapper, ChatResponse, ChatResponseWrapper,
+    };
+    use crate::messages::envelope::{
+        Envelope, ProtocolVersionRange, UnsupportedReason, LEGACY_PROTOCOL_VERSION,
+        PROTOCOL_VERSION,
+    };
+    use crate::messages::general_messages::{Codec, DroneSend, MessageKind, ServerTypeRequest};
+    use crate::messages::pending_requests::PendingRequests;
+
+    #[test]
+    fn should_open_sealed_envelopes() {
+        for codec in [Codec::Json, Codec::Binary] {
+            let raw =
+                Envelope::seal(ChatRequestWrapper::Chat(ChatRequest::Register(3)), codec).unwrap();
+            let envelope =
+                Envelope::<ChatRequestWrapper>::open(&raw, ProtocolVersionRange::current())
+                    .unwrap();
+
+            assert_eq!(envelope.version, PROTOCOL_VERSION);
+            assert_eq!(envelope.kind, "ChatRequestWrapper");
+            assert!(matches!(
+                envelope.payload,
+                ChatRequestWrapper::Chat(ChatRequest::Register(3))
+            ));
+        }
+    }
+
+    #[test]
+    fn should_accept_messages_without_envelope() {
+        let raw = ChatRequestWrapper::ServerType(ServerTypeRequest::ServerType).to_bytes();
+        let envelope =
+            Envelope::<ChatRequestWrapper>::open(&raw.unwrap(), ProtocolVersionRange::current())
+                .unwrap();
+        assert_eq!(envelope.version, LEGACY_PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn should_answer_unsupported_messages() {
+        let supported = ProtocolVersionRange::current();
+
+        let mut envelope = Envelope::new(ChatRequestWrapper::Chat(ChatRequest::ClientList));
+        envelope.version = PROTOCOL_VERSION + 1;
+        let unsupported =
+            Envelope::<ChatRequestWrapper>::open(&envelope.to_bytes().unwrap(), supported)
+                .unwrap_err();
+        assert_eq!(unsupported.version, PROTOCOL_VERSION + 1);
+        assert_eq!(unsupported.reason, UnsupportedReason::Version);
+        assert_eq!(unsupported.supported, supported);
+
+        let raw = Envelope::seal(
+            ChatRequestWrapper::Chat(ChatRequest::ClientList),
+            Codec::Json,
+        );
+        let unsupported =
+            Envelope::<BrowserRequestWrapper>::open(&raw.unwrap(), supported).unwrap_err();
+        assert_eq!(unsupported.kind, "ChatRequestWrapper");
+        assert_eq!(unsupported.reason, UnsupportedReason::Kind);
+
+        // A variant added in a newer version of the protocol
+        let raw =
+            br#"{"version":1,"kind":"ChatRequestWrapper","payload":{"Chat":{"CreateRoom":"a"}}}"#;
+        let unsupported = Envelope::<ChatRequestWrapper>::open(raw, supported).unwrap_err();
+        assert!(matches!(
+            unsupported.reason,
+            UnsupportedReason::InvalidMessage(_)
+        ));
+
+        // The error can be sent back to the client
+        let response = ChatResponseWrapper::Unsupported(unsupported);
+        assert!(ChatResponseWrapper::from_bytes(&response.to_bytes().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn should_negotiate_version() {
+        let server = ProtocolVersionRange { min: 1, max: 3 };
+        assert_eq!(
+            server.negotiate(&ProtocolVersionRange { min: 0, max: 2 }),
+            Some(2)
+        );
+        assert_eq!(
+            server.negotiate(&ProtocolVersionRange { min: 4, max: 5 }),
+            None
+        );
+    }
+
+    #[test]
+    fn should_echo_correlation_id() {
+        let request = Envelope::request(ChatRequestWrapper::Chat(ChatRequest::ClientList), 7);
+        let raw = request.encode(Codec::Binary).unwrap();
+        let received =
+            Envelope::<ChatRequestWrapper>::open(&raw, ProtocolVersionRange::current()).unwrap();
+        assert_eq!(received.correlation_id, Some(7));
+
+        let response = received.reply(ChatResponseWrapper::Chat(ChatResponse::ClientList(vec![])));
+        assert_eq!(response.correlation_id, Some(7));
+        assert_eq!(response.kind, "ChatResponseWrapper");
+
+        // Unsupported messages echo the id too
+        let mut request = request;
+        request.version = PROTOCOL_VERSION + 1;
+        let unsupported = Envelope::<ChatRequestWrapper>::open(
+            &request.to_bytes().unwrap(),
+            ProtocolVersionRange::current(),
+        )
+        .unwrap_err();
+        assert_eq!(unsupported.correlation_id, Some(7));
+    }
+
+    #[test]
+    fn should_match_responses_to_pending_requests() {
+        let start = Instant::now();
+        let mut pending = PendingRequests::with_timeout(Duration::from_millis(100));
+
+        let first = pending.register_at("text file 1", start);
+        let second = pending.register_at("text file 2", start + Duration::from_millis(50));
+        assert_ne!(first, second);
+        assert_eq!(pending.len(), 2);
+
+        assert_eq!(pending.resolve(second), Some("text file 2"));
+        assert_eq!(pending.resolve(second), None);
+
+        let third = pending.register_at("client list", start + Duration::from_millis(80));
+        let expired = pending.sweep_expired(start + Duration::from_millis(120));
+        assert_eq!(expired, vec![(first, "text file 1")]);
+        assert_eq!(pending.get(third), Some(&"client list"));
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn should_use_stable_message_kinds() {
+        assert_eq!(ChatRequestWrapper::KIND, "ChatRequestWrapper");
+        assert_eq!(Envelope::<ChatRequestWrapper>::KIND, "Envelope");
+    }
+}
@@ -0,0 +1,153 @@
+#[cfg(test)]
+pub mod general_messages_tests {
+    use std::collections::HashMap;
+
+    use wg_2024::network::SourceRoutingHeader;
+
+    use crate::assembler::{disassembler::Disassembler, message_assembler::MessageAssembler};
+    use crate::messages::browser_messages::{BrowserResponse, BrowserResponseWrapper, FileId};
+    use crate::messages::chat_messages::ChatResponseWrapper;
+    use crate::messages::commander_messages::SimControllerMessage;
+    use crate::messages::general_messages::{
+        Codec, DroneSend, DroneSendError, ErrorCode, ErrorResponse, ServerType, BINARY_MAGIC,
+        BINARY_VERSION,
+    };
+
+    #[test]
+    fn should_encode_with_both_codecs() {
+        let response = BrowserResponse::MediaFile(FileId(4), (0..=255).collect());
+
+        let json = response.encode(Codec::Json).unwrap();
+        let binary = response.encode(Codec::Binary).unwrap();
+        assert_eq!(json, response.stringify().into_bytes());
+        assert_eq!(&binary[..2], &[BINARY_MAGIC, BINARY_VERSION]);
+        assert!(binary.len() * 2 < json.len());
+
+        assert_eq!(Codec::detect(&json), Codec::Json);
+        assert_eq!(Codec::detect(&binary), Codec::Binary);
+        for raw in [json, binary] {
+            match BrowserResponse::decode(&raw).unwrap() {
+                BrowserResponse::MediaFile(FileId(4), data) => {
+                    assert_eq!(data, (0..=255).collect::<Vec<u8>>())
+                }
+                other => panic!("unexpected response {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn should_encode_maps_in_binary() {
+        let message = SimControllerMessage::KnownServers(HashMap::from([
+            (1, ServerType::Chat),
+            (2, ServerType::Media),
+        ]));
+        let raw = message.encode(Codec::Binary).unwrap();
+
+        match SimControllerMessage::decode(&raw).unwrap() {
+            SimControllerMessage::KnownServers(servers) => assert_eq!(servers.len(), 2),
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn should_reject_unknown_binary_version() {
+        let mut raw = BrowserResponse::FileList(vec![FileId(1), FileId(2)])
+            .encode(Codec::Binary)
+            .unwrap();
+        raw[1] = BINARY_VERSION + 1;
+        assert_eq!(
+            BrowserResponse::decode(&raw).unwrap_err(),
+            DroneSendError::UnsupportedVersion(BINARY_VERSION + 1)
+        );
+    }
+
+    #[test]
+    fn should_send_binary_messages_through_fragments() {
+        let response =
+            BrowserResponseWrapper::Chat(BrowserResponse::MediaFile(FileId(1), vec![9; 1000]));
+        let header = SourceRoutingHeader {
+            hop_index: 1,
+            hops: vec![30, 1, 10],
+        };
+
+        let mut disassembler = Disassembler::new();
+        let json_packets = disassembler
+            .disassemble_packets(&response, header.clone(), 1)
+            .unwrap();
+        disassembler.set_codec(Codec::Binary);
+        let packets = disassembler
+            .disassemble_packets(&response, header, 1)
+            .unwrap();
+        assert!(packets.len() < json_packets.len());
+
+        let mut assembler = MessageAssembler::<BrowserResponseWrapper>::new();
+        let mut result = None;
+        for packet in &packets {
+            result = assembler.handle_packet(packet).unwrap();
+        }
+        assert!(matches!(
+            result.unwrap().content,
+            BrowserResponseWrapper::Chat(BrowserResponse::MediaFile(FileId(1), data)) if data == vec![9; 1000]
+        ));
+    }
+
+    #[test]
+    fn should_return_error_instead_of_panicking() {
+        // JSON maps need string keys, a map with tuple keys can not be serialized
+        #[derive(Debug, serde::Serialize, serde::Deserialize)]
+        struct Routes(HashMap<(u8, u8), Vec<u8>>);
+        impl DroneSend for Routes {}
+
+        let routes = Routes(HashMap::from([((1, 2), vec![1, 5, 2])]));
+        assert!(matches!(
+            routes.try_stringify(),
+            Err(DroneSendError::Serialize(_))
+        ));
+        assert!(matches!(
+            routes.to_bytes(),
+            Err(DroneSendError::Serialize(_))
+        ));
+        // The binary codec has no such restriction
+        assert!(routes.encode(Codec::Binary).is_ok());
+    }
+
+    #[test]
+    fn should_deserialize_from_bytes() {
+        let raw = BrowserResponse::TextFile(FileId(2), "ciao".to_string())
+            .to_bytes()
+            .unwrap();
+        assert!(matches!(
+            BrowserResponse::from_bytes(&raw),
+            Ok(BrowserResponse::TextFile(FileId(2), text)) if text == "ciao"
+        ));
+        assert!(matches!(
+            BrowserResponse::from_bytes(b"{\"Unknown\":1}"),
+            Err(DroneSendError::Deserialize(_))
+        ));
+        assert!(matches!(
+            BrowserResponse::from_string("not json".to_string()),
+            Err(DroneSendError::Deserialize(_))
+        ));
+    }
+
+    #[test]
+    fn should_send_error_responses() {
+        let error = ErrorResponse::new(ErrorCode::NotFound, "file 3 does not exist", Some(12));
+
+        let raw = BrowserResponseWrapper::Error(error.clone())
+            .to_bytes()
+            .unwrap();
+        assert!(matches!(
+            BrowserResponseWrapper::from_bytes(&raw),
+            Ok(BrowserResponseWrapper::Error(received)) if received == error
+        ));
+
+        let raw = ChatResponseWrapper::Error(error.clone())
+            .encode(Codec::Binary)
+            .unwrap();
+        assert!(matches!(
+            ChatResponseWrapper::decode(&raw),
+            Ok(ChatResponseWrapper::Error(received)) if received == error
+        ));
+    }
+}
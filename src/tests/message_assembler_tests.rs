@@ -0,0 +1,87 @@
+#[cfg(test)]
+pub mod message_assembler_tests {
+    use wg_2024::network::SourceRoutingHeader;
+    use wg_2024::packet::{Ack, Fragment, Packet, PacketType};
+
+    use crate::assembler::{
+        disassembler::Disassembler,
+        message_assembler::{MessageAssembler, MessageAssemblerError},
+    };
+    use crate::messages::chat_messages::{ChatRequest, ChatRequestWrapper};
+    use crate::messages::general_messages::DroneSend;
+
+    /// Utility method that wraps the fragments of a payload into packets coming from `source`
+    fn packets(payload: Vec<u8>, source: u8, session_id: u64) -> Vec<Packet> {
+        Disassembler::new()
+            .disassemble_message(payload, session_id)
+            .into_iter()
+            .map(|fragment| fragment_packet(fragment, source, session_id))
+            .collect()
+    }
+
+    fn fragment_packet(fragment: Fragment, source: u8, session_id: u64) -> Packet {
+        Packet {
+            routing_header: SourceRoutingHeader {
+                hop_index: 1,
+                hops: vec![source, 1, 2],
+            },
+            session_id,
+            pack_type: PacketType::MsgFragment(fragment),
+        }
+    }
+
+    #[test]
+    fn should_return_typed_message() {
+        let request = ChatRequestWrapper::Chat(ChatRequest::SendMessage {
+            from: 10,
+            to: 20,
+            message: "hello".repeat(50).into(),
+            message_id: Some(1),
+        });
+        let mut assembler = MessageAssembler::<ChatRequestWrapper>::new();
+
+        let mut result = None;
+        for packet in packets(request.stringify().into_bytes(), 10, 42) {
+            result = assembler.handle_packet(&packet).unwrap();
+        }
+
+        let message = result.unwrap();
+        assert_eq!(message.source_id, 10);
+        assert_eq!(message.session_id, 42);
+        match message.content {
+            ChatRequestWrapper::Chat(ChatRequest::SendMessage {
+                from, to, message, ..
+            }) => {
+                assert_eq!((from, to), (10, 20));
+                assert_eq!(message.text(), Some("hello".repeat(50).as_str()));
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_payloads() {
+        let mut assembler = MessageAssembler::<ChatRequestWrapper>::new();
+
+        let packet = packets(vec![0xff, 0xfe], 10, 1).remove(0);
+        assert!(matches!(
+            assembler.handle_packet(&packet),
+            Err(MessageAssemblerError::InvalidUtf8 { session_id: 1, .. })
+        ));
+
+        let packet = packets(b"{\"Unknown\":1}".to_vec(), 10, 2).remove(0);
+        assert!(matches!(
+            assembler.handle_packet(&packet),
+            Err(MessageAssemblerError::InvalidMessage { session_id: 2, .. })
+        ));
+
+        let ack = Packet {
+            pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+            ..packet
+        };
+        assert_eq!(
+            assembler.handle_packet(&ack).unwrap_err(),
+            MessageAssemblerError::NotAFragment
+        );
+    }
+}
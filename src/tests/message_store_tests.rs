@@ -0,0 +1,81 @@
+#[cfg(test)]
+pub mod message_store_tests {
+    use crate::messages::chat_messages::StoredMessage;
+    use std::time::Duration;
+
+    use crate::messages::message_store::{MessageStore, MessageStoreConfig};
+
+    fn message(message_id: u64, from: u8, to: u8, timestamp: u64) -> StoredMessage {
+        StoredMessage {
+            message_id: Some(message_id),
+            from,
+            to,
+            timestamp,
+            message: format!("message {message_id}").into(),
+        }
+    }
+
+    #[test]
+    fn should_queue_messages_until_taken() {
+        let mut store = MessageStore::new();
+        store.store(message(1, 4, 5, 10));
+        store.enqueue(message(2, 4, 5, 20));
+        store.enqueue(message(3, 6, 5, 30));
+        assert_eq!(store.queued_len(5), 2);
+        assert_eq!(store.queued_len(4), 0);
+
+        let queued = store.take_queued(5);
+        let ids: Vec<u64> = queued.iter().filter_map(|m| m.message_id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        assert!(store.take_queued(5).is_empty());
+
+        // Queued messages are part of the history too
+        assert_eq!(store.history(5, 4, None, 10).len(), 2);
+    }
+
+    #[test]
+    fn should_return_last_messages_of_conversation() {
+        let mut store = MessageStore::new();
+        store.store(message(1, 4, 5, 10));
+        store.store(message(2, 5, 4, 20));
+        store.store(message(3, 4, 6, 30));
+        store.store(message(4, 4, 5, 40));
+
+        let ids = |messages: Vec<StoredMessage>| -> Vec<u64> {
+            messages.iter().filter_map(|m| m.message_id).collect()
+        };
+        assert_eq!(ids(store.history(4, 5, None, 10)), vec![1, 2, 4]);
+        assert_eq!(ids(store.history(5, 4, None, 2)), vec![2, 4]);
+        assert_eq!(ids(store.history(4, 5, Some(20), 10)), vec![4]);
+        assert!(store.history(5, 6, None, 10).is_empty());
+    }
+
+    #[test]
+    fn should_drop_oldest_messages_over_limits() {
+        let mut store = MessageStore::with_config(MessageStoreConfig {
+            max_history_per_conversation: 3,
+            max_history_age: Duration::from_millis(1000),
+            max_queued_per_client: 2,
+        });
+        let ids = |messages: Vec<StoredMessage>| -> Vec<u64> {
+            messages.iter().filter_map(|m| m.message_id).collect()
+        };
+
+        for id in 1..=5 {
+            store.enqueue(message(id, 4, 5, id * 10));
+        }
+        assert_eq!(ids(store.history(4, 5, None, 10)), vec![3, 4, 5]);
+        assert_eq!(ids(store.take_queued(5)), vec![4, 5]);
+        assert_eq!(store.dropped_queued(), 3);
+
+        // A newer message pushes the expired ones out of the conversation
+        store.store(message(6, 5, 4, 1045));
+        assert_eq!(ids(store.history(4, 5, None, 10)), vec![5, 6]);
+
+        // Idle conversations expire with sweep_expired
+        store.store(message(7, 6, 7, 2000));
+        store.sweep_expired(2500);
+        assert!(store.history(4, 5, None, 10).is_empty());
+        assert_eq!(ids(store.history(7, 6, None, 10)), vec![7]);
+    }
+}